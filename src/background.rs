@@ -0,0 +1,235 @@
+use crate::ray::Ray;
+use crate::sampling::Distribution2D;
+use crate::vec3::{Color, Vec3};
+
+use anyhow::{bail, Context, Result};
+use image::codecs::hdr::HdrDecoder;
+use nalgebra::Unit;
+use std::f64::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+// what a ray sees when it escapes the scene
+pub enum Background {
+    // linearly blends white and blue depending on height of y coord after scaling the ray to unit
+    Sky,
+    Color(Color),
+    Environment(EnvironmentMap),
+}
+
+impl Background {
+    pub fn value(&self, r: &Ray) -> Color {
+        match self {
+            Background::Sky => {
+                let unit_direction = Unit::new_normalize(r.direction());
+                let t = 0.5 * (unit_direction.y + 1.0);
+                (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
+            }
+            Background::Color(c) => *c,
+            Background::Environment(env) => env.value(&r.direction()),
+        }
+    }
+}
+
+// an equirectangular hdr image surrounding the scene: +y is at the top of the image, the middle
+// column looks down +x, and the left and right edges down -x
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    texels: Vec<Color>, // row by row, from the top
+    rotation: f64,      // about +y, in radians
+    intensity: f64,
+    // over image coordinates, by brightness
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    pub fn new(width: usize, height: usize, texels: Vec<Color>) -> Self {
+        assert_eq!(
+            texels.len(),
+            width * height,
+            "texel count doesn't match size"
+        );
+        assert!(width > 0 && height > 0, "environment map is empty");
+
+        // lookups are filtered, so a texel can pick up light from its neighbours; take the
+        // brightest of them, or dim texels next to bright ones get sampled far too rarely for
+        // what they return. rows near the poles are squeezed onto less of the sphere, so
+        // they're sampled less
+        let luminance = |c: &Color| 0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z;
+        let mut importance = vec![0.; width * height];
+        for (k, w) in importance.iter_mut().enumerate() {
+            let (i, j) = ((k % width) as i64, (k / width) as i64);
+            let mut brightest = 0f64;
+            for dj in -1..=1 {
+                for di in -1..=1 {
+                    let n = (i + di).rem_euclid(width as i64) as usize
+                        + (j + dj).clamp(0, height as i64 - 1) as usize * width;
+                    brightest = brightest.max(luminance(&texels[n]));
+                }
+            }
+            let theta = PI * (j as f64 + 0.5) / height as f64;
+            *w = brightest * theta.sin();
+        }
+        let distribution = Distribution2D::new(&importance, width);
+
+        Self {
+            width,
+            height,
+            texels,
+            rotation: 0.,
+            intensity: 1.,
+            distribution,
+        }
+    }
+
+    // radiance .hdr or openexr, by extension
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let load = || -> Result<Self> {
+            let (width, height, texels) = if ext.eq_ignore_ascii_case("exr") {
+                read_exr(path)?
+            } else {
+                read_hdr(path)?
+            };
+            if texels.is_empty() {
+                bail!("image is empty");
+            }
+            Ok(Self::new(width, height, texels))
+        };
+        load().with_context(|| format!("failed to load environment map {}", path.display()))
+    }
+
+    // turns the environment around the vertical axis
+    pub fn with_rotation(self, degrees: f64) -> Self {
+        Self {
+            rotation: degrees.to_radians(),
+            ..self
+        }
+    }
+
+    // scales the radiance of the whole image
+    pub fn with_intensity(self, intensity: f64) -> Self {
+        Self { intensity, ..self }
+    }
+
+    // image coordinates in [0, 1] for a world direction
+    fn uv(&self, direction: &Vec3) -> (f64, f64) {
+        let d = direction.normalize();
+        let phi = (-d.z).atan2(d.x) + PI - self.rotation;
+        let u = (phi / (2. * PI)).rem_euclid(1.);
+        let v = d.y.clamp(-1., 1.).acos() / PI;
+        (u, v)
+    }
+
+    // the inverse of uv
+    fn direction(&self, u: f64, v: f64) -> Vec3 {
+        let phi = 2. * PI * u + self.rotation - PI;
+        let theta = PI * v;
+        Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.cos(),
+            -theta.sin() * phi.sin(),
+        )
+    }
+
+    // a direction picked in proportion to how bright the environment is there, and its pdf
+    // with respect to solid angle
+    pub fn sample(&self, u0: f64, u1: f64) -> (Vec3, f64) {
+        let ((u, v), pdf) = self.distribution.sample(u0, u1);
+        let direction = self.direction(u, v);
+        (direction, self.to_solid_angle(pdf, v))
+    }
+
+    // the pdf of sample picking this direction
+    pub fn pdf(&self, direction: &Vec3) -> f64 {
+        let (u, v) = self.uv(direction);
+        self.to_solid_angle(self.distribution.pdf(u, v), v)
+    }
+
+    // the image spans 2pi by pi radians, and a row covers sin(theta) of the width it has there
+    fn to_solid_angle(&self, pdf_uv: f64, v: f64) -> f64 {
+        let sin_theta = (PI * v).sin();
+        if sin_theta <= 0. {
+            return 0.;
+        }
+        pdf_uv / (2. * PI * PI * sin_theta)
+    }
+
+    fn texel(&self, i: i64, j: i64) -> Color {
+        // around the horizon the image wraps; at the poles it doesn't
+        let i = i.rem_euclid(self.width as i64) as usize;
+        let j = j.clamp(0, self.height as i64 - 1) as usize;
+        self.texels[j * self.width + i]
+    }
+
+    // bilinearly filtered radiance arriving from a direction
+    pub fn value(&self, direction: &Vec3) -> Color {
+        let (u, v) = self.uv(direction);
+        let x = u * self.width as f64 - 0.5;
+        let y = v * self.height as f64 - 0.5;
+        let (i, j) = (x.floor(), y.floor());
+        let (fx, fy) = (x - i, y - j);
+        let (i, j) = (i as i64, j as i64);
+        let top = (1. - fx) * self.texel(i, j) + fx * self.texel(i + 1, j);
+        let bottom = (1. - fx) * self.texel(i, j + 1) + fx * self.texel(i + 1, j + 1);
+        self.intensity * ((1. - fy) * top + fy * bottom)
+    }
+}
+
+fn read_hdr(path: &Path) -> Result<(usize, usize, Vec<Color>)> {
+    let decoder = HdrDecoder::new(BufReader::new(File::open(path)?))?;
+    let meta = decoder.metadata();
+    let texels = decoder
+        .read_image_hdr()?
+        .into_iter()
+        .map(|p| Color::new(p[0] as f64, p[1] as f64, p[2] as f64))
+        .collect();
+    Ok((meta.width as usize, meta.height as usize, texels))
+}
+
+// the first layer with rgb channels; alpha is ignored
+fn read_exr(path: &Path) -> Result<(usize, usize, Vec<Color>)> {
+    let image = exr::prelude::read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| {
+            let (width, height) = (resolution.width(), resolution.height());
+            (width, height, vec![Color::zeros(); width * height])
+        },
+        |(width, _, texels), position, (r, g, b, _): (f32, f32, f32, f32)| {
+            texels[position.y() * *width + position.x()] = Color::new(r as f64, g as f64, b as f64);
+        },
+    )?;
+    Ok(image.layer_data.channel_data.pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_exr() {
+        let path = std::env::temp_dir().join(format!("rt-exr-test-{}.exr", std::process::id()));
+        // a 3x2 image, brighter to the right and down
+        exr::prelude::write_rgb_file(&path, 3, 2, |x, y| (x as f32, y as f32, 0.5)).unwrap();
+        let map = EnvironmentMap::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        let map = map.unwrap();
+        assert_eq!((map.width, map.height), (3, 2));
+        assert_eq!(map.texels[0], Color::new(0., 0., 0.5));
+        assert_eq!(map.texels[2], Color::new(2., 0., 0.5));
+        assert_eq!(map.texels[4], Color::new(1., 1., 0.5));
+    }
+
+    #[test]
+    fn missing_files_say_which() {
+        let e = EnvironmentMap::open("nowhere/sky.exr").err().unwrap();
+        assert!(
+            format!("{:#}", e).starts_with("failed to load environment map nowhere/sky.exr: "),
+            "{:#}",
+            e
+        );
+    }
+}
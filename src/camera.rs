@@ -1,6 +1,8 @@
 use crate::ray::Ray;
+use crate::sampler;
 use crate::vec3::*;
 
+#[derive(Clone)]
 pub struct Camera {
     origin: Point3,
     lower_left_corner: Point3,
@@ -10,6 +12,10 @@ pub struct Camera {
     v: Unit<Vec3>,
     w: Unit<Vec3>,
     lens_radius: f64,
+    focus_dist: f64,
+    // rays are sent at random times between these
+    time0: f64,
+    time1: f64,
 }
 
 impl Camera {
@@ -44,19 +50,67 @@ impl Camera {
             horizontal,
             vertical,
             lower_left_corner,
-            w,
             u,
             v,
+            w,
             lens_radius,
+            focus_dist,
+            time0: 0.,
+            time1: 0.,
+        }
+    }
+
+    // by default the shutter is open only for an instant, at time 0
+    pub fn with_shutter(self, time0: f64, time1: f64) -> Self {
+        Self {
+            time0,
+            time1,
+            ..self
         }
     }
 
     pub fn get_ray(&self, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * crate::vec3::random_in_unit_disk();
-        let offset = rd.x * self.u.as_ref() + rd.y * self.v.as_ref();
+        let lens = self.sample_lens();
         Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+            lens,
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - lens,
+            if self.time1 > self.time0 {
+                sampler::random_range(self.time0..self.time1)
+            } else {
+                self.time0
+            },
         )
     }
+
+    // a point on the lens, picked uniformly
+    pub fn sample_lens(&self) -> Point3 {
+        let rd = self.lens_radius * crate::vec3::random_in_unit_disk();
+        self.origin + rd.x * self.u.as_ref() + rd.y * self.v.as_ref()
+    }
+
+    // the way the camera looks
+    pub fn forward(&self) -> Unit<Vec3> {
+        -self.w
+    }
+
+    // of the viewport, moved to distance 1 from the lens
+    pub fn viewport_area(&self) -> f64 {
+        self.horizontal.magnitude() * self.vertical.magnitude()
+            / (self.focus_dist * self.focus_dist)
+    }
+
+    // the s and t get_ray would take to see p through a point on the lens; None if p is behind
+    // the camera
+    pub fn project(&self, lens: &Point3, p: &Point3) -> Option<(f64, f64)> {
+        let direction = p - lens;
+        let depth = direction.dot(&self.forward());
+        if depth <= 0. {
+            return None;
+        }
+        let on_focus_plane = lens + direction * (self.focus_dist / depth) - self.lower_left_corner;
+        Some((
+            on_focus_plane.dot(&self.horizontal) / self.horizontal.magnitude_squared(),
+            on_focus_plane.dot(&self.vertical) / self.vertical.magnitude_squared(),
+        ))
+    }
 }
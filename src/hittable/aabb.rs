@@ -1,8 +1,21 @@
-use crate::hittable::{HitRecord, Hittable};
-use crate::material::Material;
 use crate::ray::Ray;
 use crate::vec3::*;
 
+#[cfg(feature = "heatmap")]
+use std::cell::Cell;
+
+#[cfg(feature = "heatmap")]
+thread_local! {
+    // box tests made on this thread, for seeing where traversal gets expensive
+    static BOX_TESTS: Cell<u64> = const { Cell::new(0) };
+}
+
+// box tests made on this thread since the last call
+#[cfg(feature = "heatmap")]
+pub fn take_box_tests() -> u64 {
+    BOX_TESTS.with(|c| c.replace(0))
+}
+
 // axis-aligned bounding box
 
 #[derive(Clone)]
@@ -12,7 +25,7 @@ pub struct Aabb {
 }
 
 impl Aabb {
-    pub fn new(max: Point3, min: Point3) -> Self {
+    pub fn new(min: Point3, max: Point3) -> Self {
         Self { max, min }
     }
 
@@ -28,6 +41,8 @@ impl Aabb {
 
     // TODO: this is not the same as Hittable::hit. why not? its a bit ugly
     pub fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> bool {
+        #[cfg(feature = "heatmap")]
+        BOX_TESTS.with(|c| c.set(c.get() + 1));
         for a in 0..3 {
             let inv_d = 1. / r.direction()[a];
             let mut t0 = (self.min()[a] - r.origin()[a]) * inv_d;
@@ -59,4 +74,31 @@ impl Aabb {
 
         Aabb::new(small, big)
     }
+
+    pub fn centroid(&self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        2. * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    // grow the box to contain point p
+    pub fn surrounding_point(&self, p: &Point3) -> Aabb {
+        Aabb::new(self.min.inf(p), self.max.sup(p))
+    }
+
+    // widen any dimension thinner than delta, since flat boxes never pass the slab test
+    pub fn pad(&self, delta: f64) -> Aabb {
+        let mut min = self.min;
+        let mut max = self.max;
+        for a in 0..3 {
+            if max[a] - min[a] < delta {
+                min[a] -= delta / 2.;
+                max[a] += delta / 2.;
+            }
+        }
+        Aabb::new(min, max)
+    }
 }
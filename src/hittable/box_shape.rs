@@ -0,0 +1,45 @@
+use crate::hittable::{aabb::Aabb, AaRect, HitRecord, Hittable, HittableList};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+// axis-aligned box made of six rects, all sharing one material
+pub struct BoxShape {
+    min: Point3,
+    max: Point3,
+    sides: HittableList,
+}
+
+impl BoxShape {
+    pub fn new(p0: Point3, p1: Point3, material: Arc<dyn Material>) -> Self {
+        let min = p0.inf(&p1);
+        let max = p0.sup(&p1);
+
+        let mut sides = HittableList::default();
+        let x = (min.x, max.x);
+        let y = (min.y, max.y);
+        let z = (min.z, max.z);
+        // rects face their positive axis, so the ones on the min sides get turned around
+        let m = || Arc::clone(&material);
+        sides.add(Arc::new(AaRect::xy(x, y, max.z, m())));
+        sides.add(Arc::new(AaRect::xy(x, y, min.z, m()).flipped()));
+        sides.add(Arc::new(AaRect::xz(x, z, max.y, m())));
+        sides.add(Arc::new(AaRect::xz(x, z, min.y, m()).flipped()));
+        sides.add(Arc::new(AaRect::yz(y, z, max.x, m())));
+        sides.add(Arc::new(AaRect::yz(y, z, min.x, m()).flipped()));
+
+        Self { min, max, sides }
+    }
+}
+
+impl Hittable for BoxShape {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.sides.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max).pad(1e-4))
+    }
+}
@@ -1,6 +1,7 @@
 use crate::hittable::{aabb::Aabb, hittable_list::HittableList, HitRecord, Hittable};
 use crate::ray::Ray;
 
+use anyhow::{bail, Result};
 use rand::Rng;
 
 use std::sync::Arc;
@@ -13,33 +14,37 @@ pub struct BvhNode {
 }
 
 impl BvhNode {
-    pub fn new(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>, bbox: Aabb) -> Self {
-        Self { left, right, bbox }
+    // randomly choose an axis, sort the primitives, put half in each subtree
+    pub fn from_hittable_list(list: HittableList) -> Result<Self> {
+        let objects = list.into_inner();
+        if objects.is_empty() {
+            bail!("can't build a bvh over an empty scene");
+        }
+        Ok(Self::from_objects(objects))
     }
 
-    // randomly choose an axis, sort the primitives, put half in each subtree
-    pub fn from_hittable_list(list: HittableList) -> Self {
-        let axis = rand::thread_rng().gen_range(0..2);
-        let mut objects = list.into_inner();
-
-        assert!(!objects.is_empty());
-
-        let (left, right): (Arc<dyn Hittable>, Arc<dyn Hittable>) = if objects.len() == 1 {
-            (objects[0].clone(), objects[0].clone())
-        } else {
-            objects.sort_unstable_by(|a, b| {
-                // idk what we do here if no bbs like eg an infinite plane
-                // TODO: this constraint should be in the type system
-                let a_key = a.bounding_box().unwrap().min()[axis];
-                let b_key = b.bounding_box().unwrap().min()[axis];
-                a_key.partial_cmp(&b_key).unwrap()
-            });
-
-            let (l, r) = objects.split_at(objects.len());
-            (
-                Arc::new(BvhNode::from_hittable_list(HittableList::new(l.to_owned()))),
-                Arc::new(BvhNode::from_hittable_list(HittableList::new(r.to_owned()))),
-            )
+    // objects must not be empty
+    fn from_objects(mut objects: Vec<Arc<dyn Hittable>>) -> Self {
+        let axis = rand::thread_rng().gen_range(0..3);
+
+        let (left, right): (Arc<dyn Hittable>, Arc<dyn Hittable>) = match objects.len() {
+            1 => (objects[0].clone(), objects[0].clone()),
+            2 => (objects[0].clone(), objects[1].clone()),
+            _ => {
+                objects.sort_unstable_by(|a, b| {
+                    // idk what we do here if no bbs like eg an infinite plane
+                    // TODO: this constraint should be in the type system
+                    let a_key = a.bounding_box().unwrap().min()[axis];
+                    let b_key = b.bounding_box().unwrap().min()[axis];
+                    a_key.partial_cmp(&b_key).unwrap()
+                });
+
+                let r = objects.split_off(objects.len() / 2);
+                (
+                    Arc::new(BvhNode::from_objects(objects)),
+                    Arc::new(BvhNode::from_objects(r)),
+                )
+            }
         };
 
         let bbox = left
@@ -52,7 +57,15 @@ impl BvhNode {
 
 impl Hittable for BvhNode {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        todo!()
+        if !self.bbox.hit(r, t_min, t_max) {
+            return None;
+        }
+
+        let hit_left = self.left.hit(r, t_min, t_max);
+        let max = hit_left.as_ref().map_or(t_max, |hr| hr.t());
+        let hit_right = self.right.hit(r, t_min, max);
+
+        hit_right.or(hit_left)
     }
 
     fn bounding_box(&self) -> Option<Aabb> {
@@ -0,0 +1,65 @@
+use crate::hittable::{aabb::Aabb, HitRecord, Hittable};
+use crate::material::{Isotropic, Material};
+use crate::ray::Ray;
+use crate::sampler;
+use crate::texture::Texture;
+use crate::vec3::*;
+
+use nalgebra::Unit;
+use std::sync::Arc;
+
+// a volume of uniform density filling the boundary, which has to be closed and convex: a ray
+// is assumed to enter it once and leave it once
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, albedo: Arc<dyn Texture>) -> Self {
+        Self {
+            boundary,
+            neg_inv_density: -1. / density,
+            phase_function: Arc::new(Isotropic::new(albedo)),
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // where the whole line enters and leaves the boundary, then clipped to the interval.
+        // starting inside the volume, the entry is behind the ray origin
+        let enter = self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hit(r, enter.t() + 0.0001, f64::INFINITY)?;
+        let t0 = enter.t().max(t_min).max(0.);
+        let t1 = exit.t().min(t_max);
+        if t0 >= t1 {
+            return None;
+        }
+
+        // sample a free-flight distance; past the exit the ray goes straight through
+        let ray_length = r.direction().magnitude();
+        let distance_inside = (t1 - t0) * ray_length;
+        let hit_distance = self.neg_inv_density * sampler::random().ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t0 + hit_distance / ray_length;
+        // the normal doesn't mean anything inside a volume
+        Some(HitRecord::from_ray_outward_normal_material(
+            r.at(t),
+            t,
+            r,
+            Unit::new_unchecked(Vec3::x()),
+            0.,
+            0.,
+            Arc::clone(&self.phase_function),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.boundary.bounding_box()
+    }
+}
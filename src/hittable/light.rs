@@ -0,0 +1,77 @@
+use crate::hittable::{aabb::Aabb, HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::sampler;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+// an emitting object that's also sampled directly. hits on it are tagged with its index among
+// the lights, so light reaching it by other paths isn't counted twice
+struct LightSource {
+    object: Arc<dyn Hittable>,
+    index: usize,
+}
+
+impl LightSource {
+    fn new(object: Arc<dyn Hittable>, index: usize) -> Self {
+        Self { object, index }
+    }
+}
+
+impl Hittable for LightSource {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.object
+            .hit(r, t_min, t_max)
+            .map(|rec| rec.with_light(self.index))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.object.bounding_box()
+    }
+}
+
+// the lights of a scene, picked between uniformly
+#[derive(Default)]
+pub struct Lights {
+    objects: Vec<Arc<dyn Hittable>>,
+}
+
+impl Lights {
+    // returns the object to put in the world in its place
+    pub fn add(&mut self, object: Arc<dyn Hittable>) -> Arc<dyn Hittable> {
+        self.objects.push(Arc::clone(&object));
+        Arc::new(LightSource::new(object, self.objects.len() - 1))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    // -> index of the light, direction towards it, and the pdf of both together
+    pub fn sample(&self, origin: &Point3) -> (usize, Vec3, f64) {
+        let i = sampler::random_index(self.objects.len());
+        let direction = self.objects[i].random(origin);
+        (i, direction, self.pdf(i, origin, &direction))
+    }
+
+    // a point picked on one of the lights, tagged with which, and its pdf with respect to area
+    pub fn sample_point(&self) -> Option<(HitRecord, f64)> {
+        if self.objects.is_empty() {
+            return None;
+        }
+        let i = sampler::random_index(self.objects.len());
+        let rec = self.objects[i].sample_surface()?;
+        let pdf = self.pdf_point(i, &rec);
+        Some((rec.with_light(i), pdf))
+    }
+
+    // of sample_point picking the point rec is at on light i
+    pub fn pdf_point(&self, i: usize, rec: &HitRecord) -> f64 {
+        self.objects[i].pdf_surface(rec) / self.objects.len() as f64
+    }
+
+    // of sample picking light i and then this direction
+    pub fn pdf(&self, i: usize, origin: &Point3, direction: &Vec3) -> f64 {
+        self.objects[i].pdf_value(origin, direction) / self.objects.len() as f64
+    }
+}
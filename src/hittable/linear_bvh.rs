@@ -0,0 +1,209 @@
+use crate::hittable::sah_bvh::SahNode;
+use crate::hittable::{aabb::Aabb, hittable_list::HittableList, HitRecord, Hittable};
+use crate::ray::Ray;
+
+use anyhow::Result;
+use std::sync::Arc;
+
+// deep enough for the trees the sah builder makes of most scenes; deeper ones spill onto the heap
+const STACK_SIZE: usize = 64;
+
+// a bvh flattened into an array in depth-first order. the first child of an interior node is
+// the node right after it; the second child is at `offset`. traversal uses an explicit stack.
+// see pbrt 3rd ed., section 4.3.4
+struct LinearNode {
+    bbox: Aabb,
+    // leaf: index of the first primitive. interior: index of the second child
+    offset: usize,
+    // 0 for interior nodes
+    n_primitives: usize,
+    // interior: the axis the children were split along
+    axis: usize,
+}
+
+// nodes still to visit. a fixed array, with a vec for whatever doesn't fit, which doesn't
+// allocate until it's used
+struct NodeStack {
+    nodes: [usize; STACK_SIZE],
+    len: usize,
+    overflow: Vec<usize>,
+}
+
+impl NodeStack {
+    fn new() -> Self {
+        Self {
+            nodes: [0; STACK_SIZE],
+            len: 0,
+            overflow: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, node: usize) {
+        if self.len < STACK_SIZE {
+            self.nodes[self.len] = node;
+            self.len += 1;
+        } else {
+            self.overflow.push(node);
+        }
+    }
+
+    // the overflow only fills once the array is full, so it's the top of the stack
+    fn pop(&mut self) -> Option<usize> {
+        if let Some(node) = self.overflow.pop() {
+            return Some(node);
+        }
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(self.nodes[self.len])
+    }
+}
+
+pub struct LinearBvh {
+    nodes: Vec<LinearNode>,
+    primitives: Vec<Arc<dyn Hittable>>,
+}
+
+impl LinearBvh {
+    pub fn from_hittable_list(list: HittableList) -> Result<Self> {
+        let root = SahNode::build(list.into_inner())?;
+        let mut bvh = Self {
+            nodes: vec![],
+            primitives: vec![],
+        };
+        bvh.flatten(root);
+        Ok(bvh)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn primitive_count(&self) -> usize {
+        self.primitives.len()
+    }
+
+    // append node (and its subtree) in depth-first order, returning its index
+    fn flatten(&mut self, node: SahNode) -> usize {
+        let idx = self.nodes.len();
+        match node {
+            SahNode::Leaf { bbox, objects } => {
+                self.nodes.push(LinearNode {
+                    bbox,
+                    offset: self.primitives.len(),
+                    n_primitives: objects.len(),
+                    axis: 0,
+                });
+                self.primitives.extend(objects);
+            }
+            SahNode::Interior {
+                bbox,
+                axis,
+                left,
+                right,
+            } => {
+                self.nodes.push(LinearNode {
+                    bbox,
+                    offset: 0,
+                    n_primitives: 0,
+                    axis,
+                });
+                self.flatten(*left);
+                self.nodes[idx].offset = self.flatten(*right);
+            }
+        }
+        idx
+    }
+}
+
+impl Hittable for LinearBvh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest: Option<HitRecord> = None;
+        let mut stack = NodeStack::new();
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+            let max = closest.as_ref().map_or(t_max, |hr| hr.t());
+            // a single primitive's own test is no more expensive than the box test
+            let visit = node.n_primitives == 1 || node.bbox.hit(r, t_min, max);
+
+            if visit && node.n_primitives > 0 {
+                for o in &self.primitives[node.offset..node.offset + node.n_primitives] {
+                    let max = closest.as_ref().map_or(t_max, |hr| hr.t());
+                    if let shr @ Some(_) = o.hit(r, t_min, max) {
+                        closest = shr;
+                    }
+                }
+            } else if visit {
+                // the child nearer the ray's origin first, so that hits in it cut the other short
+                if r.direction()[node.axis] < 0. {
+                    stack.push(current + 1);
+                    current = node.offset;
+                } else {
+                    stack.push(node.offset);
+                    current += 1;
+                }
+                continue;
+            }
+
+            match stack.pop() {
+                Some(node) => current = node,
+                None => break,
+            }
+        }
+
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.nodes[0].bbox.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::sah_bvh::tests::{assert_same_hits, random_objects, random_rays};
+    use crate::hittable::{SahBvh, Sphere};
+    use crate::material::Lambertian;
+    use crate::vec3::{Color, Point3, Vec3};
+
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn same_hits_as_a_list() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let objects = random_objects(&mut rng, 200);
+        let list = HittableList::new(objects.clone());
+        let bvh = LinearBvh::from_hittable_list(HittableList::new(objects)).unwrap();
+        assert_eq!(bvh.primitive_count(), 200);
+        assert_same_hits(&list, &bvh, random_rays(&mut rng, 2000).into_iter());
+    }
+
+    // spheres further and further apart make the sah peel a few off at a time, giving a tree
+    // deeper than the stack array
+    #[test]
+    fn deep_trees() {
+        let material = Arc::new(Lambertian::from_color(Color::new(0.5, 0.5, 0.5)));
+        let objects = (0..150)
+            .map(|i| {
+                let center = Point3::new(4f64.powi(i), 0., 0.);
+                Arc::new(Sphere::new(center, 0.25, material.clone())) as Arc<dyn Hittable>
+            })
+            .collect::<Vec<_>>();
+        let sah = SahBvh::from_hittable_list(HittableList::new(objects.clone())).unwrap();
+        assert!(sah.stats().max_depth > STACK_SIZE, "{}", sah.stats());
+
+        let list = HittableList::new(objects.clone());
+        let bvh = LinearBvh::from_hittable_list(HittableList::new(objects)).unwrap();
+        let rays = [
+            Ray::new(Point3::new(-1., 0., 0.), Vec3::x(), 0.),
+            Ray::new(Point3::new(-1., 0.1, 0.), Vec3::new(1., 0., 0.01), 0.),
+            Ray::new(Point3::new(5., 0., 0.), -Vec3::x(), 0.),
+        ];
+        assert_same_hits(&list, &bvh, rays.iter().cloned());
+    }
+}
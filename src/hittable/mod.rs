@@ -6,13 +6,35 @@ use nalgebra::Unit;
 use std::sync::Arc;
 
 mod aabb;
+mod box_shape;
 mod bvh_node;
+mod constant_medium;
 mod hittable_list;
+mod light;
+mod linear_bvh;
+mod moving_sphere;
+mod rect;
+mod sah_bvh;
 mod sphere;
+mod transform;
+mod triangle;
+#[cfg(feature = "heatmap")]
+pub use crate::hittable::aabb::take_box_tests;
 pub use crate::hittable::aabb::Aabb;
+pub use crate::hittable::box_shape::BoxShape;
+pub use crate::hittable::bvh_node::BvhNode;
+pub use crate::hittable::constant_medium::ConstantMedium;
 pub use crate::hittable::hittable_list::HittableList;
+pub use crate::hittable::light::Lights;
+pub use crate::hittable::linear_bvh::LinearBvh;
+pub use crate::hittable::moving_sphere::MovingSphere;
+pub use crate::hittable::rect::AaRect;
+pub use crate::hittable::sah_bvh::SahBvh;
 pub use crate::hittable::sphere::Sphere;
+pub use crate::hittable::transform::Transform;
+pub use crate::hittable::triangle::{Triangle, TriangleMesh};
 
+#[derive(Clone)]
 pub struct HitRecord {
     p: Point3,
     normal: Unit<Vec3>,
@@ -23,6 +45,12 @@ pub struct HitRecord {
     v: f64,
 
     front_face: bool,
+
+    // set when this is one of the lights that get sampled directly, to its index among them
+    light: Option<usize>,
+
+    // the surface's own color where it has one, like a mesh's vertex colors, for VertexColor
+    color: Option<Color>,
 }
 
 // normals are unit, and they point outwards
@@ -51,6 +79,8 @@ impl HitRecord {
             material,
             u,
             v,
+            light: None,
+            color: None,
         }
     }
     pub fn t(&self) -> f64 {
@@ -77,9 +107,64 @@ impl HitRecord {
     pub fn v(&self) -> f64 {
         self.v
     }
+
+    pub fn light(&self) -> Option<usize> {
+        self.light
+    }
+
+    pub fn with_light(self, light: usize) -> Self {
+        Self {
+            light: Some(light),
+            ..self
+        }
+    }
+
+    pub fn color(&self) -> Option<Color> {
+        self.color
+    }
+
+    pub fn with_color(self, color: Color) -> Self {
+        Self {
+            color: Some(color),
+            ..self
+        }
+    }
+
+    // the same hit, moved somewhere else; the normal has to keep facing against the ray
+    pub fn with_p_normal(self, p: Point3, normal: Unit<Vec3>) -> Self {
+        Self { p, normal, ..self }
+    }
 }
 
 pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
     fn bounding_box(&self) -> Option<Aabb>;
+
+    // whether it gives off light and can be sampled as a light with the methods below
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // for sampling the object as a light: the density with respect to solid angle of random
+    // picking direction from origin, and a direction from origin towards a random point on
+    // the object. only the shapes lights can have implement these
+    fn pdf_value(&self, _origin: &Point3, _direction: &Vec3) -> f64 {
+        0.
+    }
+    fn random(&self, _origin: &Point3) -> Vec3 {
+        Vec3::x()
+    }
+
+    // likewise, for sampling points on lights: the surface area, a point picked over it as
+    // seen from outside, and the density of picking that point with respect to area. the
+    // shapes themselves pick uniformly
+    fn area(&self) -> f64 {
+        0.
+    }
+    fn sample_surface(&self) -> Option<HitRecord> {
+        None
+    }
+    fn pdf_surface(&self, _rec: &HitRecord) -> f64 {
+        1. / self.area()
+    }
 }
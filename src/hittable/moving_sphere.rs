@@ -0,0 +1,91 @@
+use crate::hittable::{aabb::Aabb, HitRecord, Hittable, Sphere};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vec3::*;
+
+use nalgebra::Unit;
+use std::sync::Arc;
+
+// a sphere moving in a straight line from center0 at time0 to center1 at time1, resting there
+// before and after
+pub struct MovingSphere {
+    center0: Point3,
+    center1: Point3,
+    time0: f64,
+    time1: f64,
+    radius: f64,
+    material: Arc<dyn Material>,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Point3,
+        center1: Point3,
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        }
+    }
+
+    pub fn center(&self, time: f64) -> Point3 {
+        if self.time1 <= self.time0 {
+            return self.center0;
+        }
+        let f = ((time - self.time0) / (self.time1 - self.time0)).clamp(0., 1.);
+        self.center0 + f * (self.center1 - self.center0)
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let center = self.center(r.time());
+        let oc = r.origin() - center;
+        let a = r.direction().magnitude_squared();
+        let half_b = oc.dot(&r.direction());
+        let c = oc.magnitude_squared() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant < 0. {
+            return None;
+        }
+        let sqrtd = discriminant.sqrt();
+
+        // find nearest root in the acceptable range
+        let mut root = (-half_b - sqrtd) / a;
+        if root < t_min || root > t_max {
+            root = (-half_b + sqrtd) / a;
+            if root < t_min || root > t_max {
+                return None;
+            }
+        }
+
+        let rec_p = r.at(root);
+        let outward_normal = Unit::new_normalize((rec_p - center) / self.radius);
+        let (u, v) = Sphere::uv(&outward_normal);
+        Some(HitRecord::from_ray_outward_normal_material(
+            rec_p,
+            root,
+            r,
+            outward_normal,
+            u,
+            v,
+            Arc::clone(&self.material),
+        ))
+    }
+
+    // covers the sphere at both ends of its path, and so everywhere in between
+    fn bounding_box(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        let box0 = Aabb::new(self.center0 - r, self.center0 + r);
+        let box1 = Aabb::new(self.center1 - r, self.center1 + r);
+        Some(box0.surrounding_box(&box1))
+    }
+}
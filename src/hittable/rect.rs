@@ -0,0 +1,148 @@
+use crate::hittable::{aabb::Aabb, HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler;
+use crate::vec3::*;
+
+use nalgebra::Unit;
+use std::sync::Arc;
+
+// axis-aligned rectangle, lying in the plane where axis k has the value `k`.
+// a and b are the two in-plane axes, in that order; the outward normal points along +k unless
+// the rect is flipped.
+pub struct AaRect {
+    axes: (usize, usize, usize), // a, b, k
+    flipped: bool,
+    a0: f64,
+    a1: f64,
+    b0: f64,
+    b1: f64,
+    k: f64,
+    material: Arc<dyn Material>,
+}
+
+impl AaRect {
+    fn new(
+        axes: (usize, usize, usize),
+        (a0, a1): (f64, f64),
+        (b0, b1): (f64, f64),
+        k: f64,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        Self {
+            axes,
+            flipped: false,
+            a0: a0.min(a1),
+            a1: a0.max(a1),
+            b0: b0.min(b1),
+            b1: b0.max(b1),
+            k,
+            material,
+        }
+    }
+
+    // facing +z
+    pub fn xy(x: (f64, f64), y: (f64, f64), k: f64, material: Arc<dyn Material>) -> Self {
+        Self::new((0, 1, 2), x, y, k, material)
+    }
+
+    // facing +y
+    pub fn xz(x: (f64, f64), z: (f64, f64), k: f64, material: Arc<dyn Material>) -> Self {
+        Self::new((0, 2, 1), x, z, k, material)
+    }
+
+    // facing +x
+    pub fn yz(y: (f64, f64), z: (f64, f64), k: f64, material: Arc<dyn Material>) -> Self {
+        Self::new((1, 2, 0), y, z, k, material)
+    }
+
+    // the same rect, facing the other way
+    pub fn flipped(self) -> Self {
+        Self {
+            flipped: !self.flipped,
+            ..self
+        }
+    }
+
+    fn random_point(&self) -> Point3 {
+        let (a, b, k) = self.axes;
+        let mut p = Point3::zeros();
+        p[a] = sampler::random_range(self.a0..self.a1);
+        p[b] = sampler::random_range(self.b0..self.b1);
+        p[k] = self.k;
+        p
+    }
+}
+
+impl Hittable for AaRect {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (a, b, k) = self.axes;
+        let t = (self.k - r.origin()[k]) / r.direction()[k];
+        // also rejects NaN, from rays parallel to the plane
+        if !(t >= t_min && t <= t_max) {
+            return None;
+        }
+        let p = r.at(t);
+        if p[a] < self.a0 || p[a] > self.a1 || p[b] < self.b0 || p[b] > self.b1 {
+            return None;
+        }
+
+        let mut outward_normal = Vec3::zeros();
+        outward_normal[k] = if self.flipped { -1. } else { 1. };
+        Some(HitRecord::from_ray_outward_normal_material(
+            p,
+            t,
+            r,
+            Unit::new_unchecked(outward_normal),
+            (p[a] - self.a0) / (self.a1 - self.a0),
+            (p[b] - self.b0) / (self.b1 - self.b0),
+            Arc::clone(&self.material),
+        ))
+    }
+
+    // uniform over the area, converted to solid angle
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let ray = Ray::new(*origin, *direction, 0.);
+        let rec = match self.hit(&ray, 0.001, f64::INFINITY) {
+            Some(rec) => rec,
+            None => return 0.,
+        };
+        let area = (self.a1 - self.a0) * (self.b1 - self.b0);
+        let distance_squared = rec.t() * rec.t() * direction.magnitude_squared();
+        let cosine = (direction[self.axes.2] / direction.magnitude()).abs();
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.random_point() - origin
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn area(&self) -> f64 {
+        (self.a1 - self.a0) * (self.b1 - self.b0)
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        let mut normal = Vec3::zeros();
+        normal[self.axes.2] = 1.;
+        let p = self.random_point();
+        self.hit(&Ray::new(p + normal, -normal, 0.), 0.5, 1.5)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let (a, b, k) = self.axes;
+        let mut min = Point3::zeros();
+        let mut max = Point3::zeros();
+        min[a] = self.a0;
+        max[a] = self.a1;
+        min[b] = self.b0;
+        max[b] = self.b1;
+        min[k] = self.k;
+        max[k] = self.k;
+        // the box has no thickness along k, so give it some
+        Some(Aabb::new(min, max).pad(1e-4))
+    }
+}
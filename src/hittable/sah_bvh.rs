@@ -0,0 +1,370 @@
+use crate::hittable::{aabb::Aabb, hittable_list::HittableList, HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::vec3::Point3;
+
+use anyhow::{bail, Result};
+use std::fmt;
+use std::sync::Arc;
+
+// bounding volume hierarchy built with the surface area heuristic, using binned centroid splits.
+// see pbrt 3rd ed., section 4.3.2
+
+const N_BINS: usize = 12;
+// costs are relative to each other; only their ratio matters.
+// stepping through a node and testing its box is about an eighth of a primitive test, as in pbrt
+const TRAVERSAL_COST: f64 = 0.125;
+const INTERSECT_COST: f64 = 1.;
+// bigger leaves get split even where the sah says they'd be cheaper, since its guess of how
+// often each side gets hit only holds up for a few primitives
+const MAX_LEAF_SIZE: usize = 8;
+// subtrees with more primitives than this get built on the rayon pool
+const PARALLEL_THRESHOLD: usize = 4096;
+
+struct PrimInfo {
+    object: Arc<dyn Hittable>,
+    bbox: Aabb,
+    centroid: Point3,
+}
+
+pub(crate) enum SahNode {
+    Leaf {
+        bbox: Aabb,
+        objects: Vec<Arc<dyn Hittable>>,
+    },
+    Interior {
+        bbox: Aabb,
+        // the axis the children were split along
+        axis: usize,
+        left: Box<SahNode>,
+        right: Box<SahNode>,
+    },
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct BvhStats {
+    pub nodes: usize,
+    pub leaves: usize,
+    pub max_depth: usize,
+    pub primitives: usize,
+    pub max_leaf_size: usize,
+}
+
+impl fmt::Display for BvhStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let avg = if self.leaves == 0 {
+            0.
+        } else {
+            self.primitives as f64 / self.leaves as f64
+        };
+        write!(
+            f,
+            "{} nodes, {} leaves, depth {}, {} primitives ({:.2} avg / {} max per leaf)",
+            self.nodes, self.leaves, self.max_depth, self.primitives, avg, self.max_leaf_size
+        )
+    }
+}
+
+pub struct SahBvh {
+    root: SahNode,
+    stats: BvhStats,
+}
+
+impl SahBvh {
+    pub fn from_hittable_list(list: HittableList) -> Result<Self> {
+        let root = SahNode::build(list.into_inner())?;
+        let mut stats = BvhStats::default();
+        root.collect_stats(1, &mut stats);
+        Ok(Self { root, stats })
+    }
+
+    pub fn stats(&self) -> &BvhStats {
+        &self.stats
+    }
+}
+
+impl SahNode {
+    pub(crate) fn build(objects: Vec<Arc<dyn Hittable>>) -> Result<Self> {
+        if objects.is_empty() {
+            bail!("can't build a bvh over an empty scene");
+        }
+
+        let prims = objects
+            .into_iter()
+            .map(|object| {
+                // TODO: this constraint should be in the type system
+                let bbox = object
+                    .bounding_box()
+                    .expect("objects in a bvh must have a bounding box");
+                let centroid = bbox.centroid();
+                PrimInfo {
+                    object,
+                    bbox,
+                    centroid,
+                }
+            })
+            .collect();
+        Ok(Self::build_recursive(prims))
+    }
+
+    pub(crate) fn bbox(&self) -> &Aabb {
+        match self {
+            SahNode::Leaf { bbox, .. } | SahNode::Interior { bbox, .. } => bbox,
+        }
+    }
+
+    fn leaf(bbox: Aabb, prims: Vec<PrimInfo>) -> Self {
+        SahNode::Leaf {
+            bbox,
+            objects: prims.into_iter().map(|p| p.object).collect(),
+        }
+    }
+
+    fn build_recursive(mut prims: Vec<PrimInfo>) -> Self {
+        let n = prims.len();
+        let bbox = union_all(prims.iter().map(|p| &p.bbox));
+        if n == 1 {
+            return Self::leaf(bbox, prims);
+        }
+
+        let centroid_bounds = prims
+            .iter()
+            .skip(1)
+            .fold(Aabb::new(prims[0].centroid, prims[0].centroid), |b, p| {
+                b.surrounding_point(&p.centroid)
+            });
+        let extent = centroid_bounds.max() - centroid_bounds.min();
+        let axis = extent.imax();
+
+        let right = if extent[axis] <= 0. {
+            // all centroids coincide, so no split can separate them
+            if n <= MAX_LEAF_SIZE {
+                return Self::leaf(bbox, prims);
+            }
+            prims.split_off(n / 2)
+        } else {
+            let axis_min = centroid_bounds.min()[axis];
+            let bin_of = |p: &PrimInfo| {
+                let b = (N_BINS as f64 * (p.centroid[axis] - axis_min) / extent[axis]) as usize;
+                b.min(N_BINS - 1)
+            };
+
+            let mut counts = [0usize; N_BINS];
+            let mut bounds: [Option<Aabb>; N_BINS] = Default::default();
+            for p in prims.iter() {
+                let b = bin_of(p);
+                counts[b] += 1;
+                bounds[b] = Some(union(bounds[b].take(), &p.bbox));
+            }
+
+            // sweep from both ends so every split candidate costs O(1)
+            let mut below = [(0usize, 0.); N_BINS - 1];
+            let (mut count, mut acc) = (0, None);
+            for i in 0..N_BINS - 1 {
+                count += counts[i];
+                if let Some(b) = &bounds[i] {
+                    acc = Some(union(acc, b));
+                }
+                below[i] = (count, acc.as_ref().map_or(0., Aabb::surface_area));
+            }
+            let mut above = [(0usize, 0.); N_BINS - 1];
+            let (mut count, mut acc) = (0, None);
+            for i in (1..N_BINS).rev() {
+                count += counts[i];
+                if let Some(b) = &bounds[i] {
+                    acc = Some(union(acc, b));
+                }
+                above[i - 1] = (count, acc.as_ref().map_or(0., Aabb::surface_area));
+            }
+
+            let parent_area = bbox.surface_area();
+            let (best_split, best_cost) = below
+                .iter()
+                .zip(above.iter())
+                .enumerate()
+                .filter(|(_, ((nb, _), (na, _)))| *nb > 0 && *na > 0)
+                .map(|(i, ((nb, ab), (na, aa)))| {
+                    let cost = TRAVERSAL_COST
+                        + INTERSECT_COST * (*nb as f64 * ab + *na as f64 * aa) / parent_area;
+                    (i, cost)
+                })
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .expect("centroid extent is nonzero so some split has both sides populated");
+
+            let leaf_cost = INTERSECT_COST * n as f64;
+            if n <= MAX_LEAF_SIZE && leaf_cost <= best_cost {
+                return Self::leaf(bbox, prims);
+            }
+
+            let (left, right): (Vec<_>, Vec<_>) =
+                prims.into_iter().partition(|p| bin_of(p) <= best_split);
+            prims = left;
+            right
+        };
+
+        let (left, right) = if n > PARALLEL_THRESHOLD {
+            rayon::join(
+                || Self::build_recursive(prims),
+                || Self::build_recursive(right),
+            )
+        } else {
+            (Self::build_recursive(prims), Self::build_recursive(right))
+        };
+
+        SahNode::Interior {
+            bbox,
+            axis,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    fn collect_stats(&self, depth: usize, stats: &mut BvhStats) {
+        stats.nodes += 1;
+        stats.max_depth = stats.max_depth.max(depth);
+        match self {
+            SahNode::Leaf { objects, .. } => {
+                stats.leaves += 1;
+                stats.primitives += objects.len();
+                stats.max_leaf_size = stats.max_leaf_size.max(objects.len());
+            }
+            SahNode::Interior { left, right, .. } => {
+                left.collect_stats(depth + 1, stats);
+                right.collect_stats(depth + 1, stats);
+            }
+        }
+    }
+
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        match self {
+            SahNode::Leaf { bbox, objects } => {
+                if !bbox.hit(r, t_min, t_max) {
+                    return None;
+                }
+                let mut closest: Option<HitRecord> = None;
+                for o in objects.iter() {
+                    let max = closest.as_ref().map_or(t_max, |hr| hr.t());
+                    if let shr @ Some(_) = o.hit(r, t_min, max) {
+                        closest = shr;
+                    }
+                }
+                closest
+            }
+            SahNode::Interior {
+                bbox, left, right, ..
+            } => {
+                if !bbox.hit(r, t_min, t_max) {
+                    return None;
+                }
+                let hit_left = left.hit(r, t_min, t_max);
+                let max = hit_left.as_ref().map_or(t_max, |hr| hr.t());
+                let hit_right = right.hit(r, t_min, max);
+                hit_right.or(hit_left)
+            }
+        }
+    }
+}
+
+fn union(acc: Option<Aabb>, b: &Aabb) -> Aabb {
+    match acc {
+        Some(a) => a.surrounding_box(b),
+        None => b.clone(),
+    }
+}
+
+fn union_all<'a>(mut boxes: impl Iterator<Item = &'a Aabb>) -> Aabb {
+    let first = boxes.next().expect("no boxes to union").clone();
+    boxes.fold(first, |acc, b| acc.surrounding_box(b))
+}
+
+impl Hittable for SahBvh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.root.hit(r, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Some(self.root.bbox().clone())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::hittable::{Sphere, Triangle};
+    use crate::material::Lambertian;
+    use crate::vec3::{Color, Vec3};
+
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    // spheres and triangles scattered around a 10 wide box at the origin
+    pub(crate) fn random_objects(rng: &mut StdRng, n: usize) -> Vec<Arc<dyn Hittable>> {
+        let material = Arc::new(Lambertian::from_color(Color::new(0.5, 0.5, 0.5)));
+        let mut point = || Point3::from_fn(|_, _| rng.gen_range(-5.0..5.0));
+        (0..n)
+            .map(|i| {
+                if i % 2 == 0 {
+                    let radius = 0.05 + 0.3 * (i % 7) as f64 / 7.;
+                    Arc::new(Sphere::new(point(), radius, material.clone())) as Arc<dyn Hittable>
+                } else {
+                    let p0 = point();
+                    let (p1, p2) = (p0 + (point() / 5.), p0 + (point() / 5.));
+                    Arc::new(Triangle::new(p0, p1, p2, material.clone()))
+                }
+            })
+            .collect()
+    }
+
+    // both find the same closest hit, or neither does
+    pub(crate) fn assert_same_hits(
+        expected: &dyn Hittable,
+        actual: &dyn Hittable,
+        rays: impl Iterator<Item = Ray>,
+    ) {
+        for r in rays {
+            let want = expected.hit(&r, 0.001, f64::INFINITY);
+            let got = actual.hit(&r, 0.001, f64::INFINITY);
+            match (want, got) {
+                (None, None) => {}
+                (Some(want), Some(got)) => {
+                    assert!(
+                        (want.t() - got.t()).abs() < 1e-9,
+                        "{} vs {}",
+                        want.t(),
+                        got.t()
+                    );
+                }
+                (want, got) => panic!(
+                    "hit at {:?}, bvh hit at {:?}",
+                    want.map(|h| h.t()),
+                    got.map(|h| h.t())
+                ),
+            }
+        }
+    }
+
+    pub(crate) fn random_rays(rng: &mut StdRng, n: usize) -> Vec<Ray> {
+        (0..n)
+            .map(|_| {
+                let origin = Point3::from_fn(|_, _| rng.gen_range(-8.0..8.0));
+                let direction = Vec3::from_fn(|_, _| rng.gen_range(-1.0..1.0));
+                Ray::new(origin, direction, 0.)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_hits_as_a_list() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let objects = random_objects(&mut rng, 200);
+        let list = HittableList::new(objects.clone());
+        let bvh = SahBvh::from_hittable_list(HittableList::new(objects)).unwrap();
+        assert_eq!(bvh.stats().primitives, 200);
+        assert!(bvh.stats().max_leaf_size <= MAX_LEAF_SIZE);
+        assert_same_hits(&list, &bvh, random_rays(&mut rng, 2000).into_iter());
+    }
+
+    #[test]
+    fn empty_scene_is_an_error() {
+        assert!(SahBvh::from_hittable_list(HittableList::default()).is_err());
+    }
+}
@@ -1,9 +1,11 @@
 use crate::hittable::{aabb::Aabb, HitRecord, Hittable};
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::sampler;
 use crate::vec3::*;
 
 use nalgebra::Unit;
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 pub struct Sphere {
@@ -23,7 +25,7 @@ impl Sphere {
 
     // p = point on sphere of radius 1, centered at origin
     // v,u in [0,1]
-    fn uv(p: &Point3) -> (f64, f64) {
+    pub(super) fn uv(p: &Point3) -> (f64, f64) {
         let theta = (-p.y).acos();
         let phi = (-p.z).atan2(p.x) + std::f64::consts::PI;
         let u = phi / (2. * std::f64::consts::PI);
@@ -68,6 +70,52 @@ impl Hittable for Sphere {
         Some(rec)
     }
 
+    // uniform over the cone of directions the sphere covers
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let ray = Ray::new(*origin, *direction, 0.);
+        if self.hit(&ray, 0.001, f64::INFINITY).is_none() {
+            return 0.;
+        }
+        let distance_squared = (self.center - origin).magnitude_squared();
+        if distance_squared <= self.radius * self.radius {
+            // from inside, the sphere covers every direction
+            return 1. / (4. * PI);
+        }
+        let cos_theta_max = (1. - self.radius * self.radius / distance_squared).sqrt();
+        1. / (2. * PI * (1. - cos_theta_max))
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        let direction = self.center - origin;
+        let distance_squared = direction.magnitude_squared();
+        if distance_squared <= self.radius * self.radius {
+            return random_unit().into_inner();
+        }
+        let (r1, r2) = (sampler::random(), sampler::random());
+        let cos_theta_max = (1. - self.radius * self.radius / distance_squared).sqrt();
+        let z = 1. + r2 * (cos_theta_max - 1.);
+        let phi = 2. * PI * r1;
+        let sin_theta = (1. - z * z).sqrt();
+        from_onb(
+            &Unit::new_normalize(direction),
+            &Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, z),
+        )
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.material.is_emissive()
+    }
+
+    fn area(&self) -> f64 {
+        4. * PI * self.radius * self.radius
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        let normal = random_unit().into_inner();
+        let p = self.center + self.radius * normal;
+        self.hit(&Ray::new(p + normal, -normal, 0.), 0.5, 1.5)
+    }
+
     fn bounding_box(&self) -> Option<Aabb> {
         Some(Aabb::new(
             self.center - Vec3::new(self.radius, self.radius, self.radius),
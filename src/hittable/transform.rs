@@ -0,0 +1,236 @@
+use crate::hittable::{aabb::Aabb, HitRecord, Hittable};
+use crate::ray::Ray;
+use crate::vec3::*;
+
+use nalgebra::{Matrix3, Matrix4, Vector4};
+use std::sync::Arc;
+
+// places an object in the world by an affine transform, so one object can be instanced many
+// times without copying it. rays are taken into object space and hits are brought back out.
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    to_world: Matrix4<f64>,
+    to_object: Matrix4<f64>,
+    // normals go out by the inverse transpose
+    normal_to_world: Matrix3<f64>,
+    bbox: Option<Aabb>,
+}
+
+impl Transform {
+    // the matrix takes object space to world space. panics if it isn't invertible
+    pub fn new(object: Arc<dyn Hittable>, to_world: Matrix4<f64>) -> Self {
+        let to_object = to_world
+            .try_inverse()
+            .expect("transform matrix must be invertible");
+        let normal_to_world = Matrix3::from_fn(|r, c| to_object[(c, r)]);
+
+        // the world box has to hold all 8 corners of the object box
+        let bbox = object.bounding_box().map(|b| {
+            let corners = (0..8).map(|i| {
+                let pick = |a: usize| {
+                    if i & (1 << a) == 0 {
+                        b.min()[a]
+                    } else {
+                        b.max()[a]
+                    }
+                };
+                transform_point(&to_world, &Point3::new(pick(0), pick(1), pick(2)))
+            });
+            let first = Aabb::new(
+                Point3::repeat(f64::INFINITY),
+                Point3::repeat(-f64::INFINITY),
+            );
+            corners.fold(first, |bb, p| bb.surrounding_point(&p))
+        });
+
+        Self {
+            object,
+            to_world,
+            to_object,
+            normal_to_world,
+            bbox,
+        }
+    }
+
+    // how much a little bit of surface with this normal in object space grows in world space
+    fn area_scale(&self, normal: &Unit<Vec3>) -> f64 {
+        let linear = Matrix3::from_fn(|r, c| self.to_world[(r, c)]);
+        linear.determinant().abs() * (self.normal_to_world * normal.into_inner()).magnitude()
+    }
+
+    fn to_object_ray(&self, r: &Ray) -> Ray {
+        // the direction isn't renormalized, so t means the same thing in both spaces
+        Ray::new(
+            transform_point(&self.to_object, &r.origin()),
+            self.to_object.transform_vector(&r.direction()),
+            r.time(),
+        )
+    }
+
+    // a hit in object space, brought out into the world
+    fn to_world_hit(&self, rec: HitRecord) -> HitRecord {
+        let p = transform_point(&self.to_world, &rec.p());
+        let normal = Unit::new_normalize(self.normal_to_world * rec.normal().into_inner());
+        rec.with_p_normal(p, normal)
+    }
+
+    // and the other way
+    fn to_object_hit(&self, rec: HitRecord) -> HitRecord {
+        let p = transform_point(&self.to_object, &rec.p());
+        let normal_to_object = Matrix3::from_fn(|r, c| self.to_world[(c, r)]);
+        let normal = Unit::new_normalize(normal_to_object * rec.normal().into_inner());
+        rec.with_p_normal(p, normal)
+    }
+}
+
+fn transform_point(m: &Matrix4<f64>, p: &Point3) -> Point3 {
+    let p = m * Vector4::new(p.x, p.y, p.z, 1.);
+    Point3::new(p.x, p.y, p.z)
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let rec = self.object.hit(&self.to_object_ray(r), t_min, t_max)?;
+        Some(self.to_world_hit(rec))
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        self.bbox.clone()
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.object.is_emissive()
+    }
+
+    // the object's density, turned into one over its surface, moved into the world, and
+    // turned back into one over directions
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let r = self.to_object_ray(&Ray::new(*origin, *direction, 0.));
+        let pdf = self.object.pdf_value(&r.origin(), &r.direction());
+        if pdf <= 0. {
+            return 0.;
+        }
+        let rec = match self.object.hit(&r, 0.001, f64::INFINITY) {
+            Some(rec) => rec,
+            None => return 0.,
+        };
+        let cosine = |d: &Vec3, normal: &Unit<Vec3>| d.normalize().dot(normal).abs();
+        let object_distance_squared = (rec.t() * r.direction()).magnitude_squared();
+        let pdf_area = pdf * cosine(&r.direction(), &rec.normal())
+            / object_distance_squared
+            / self.area_scale(&rec.normal());
+
+        let world = self.to_world_hit(rec);
+        let distance_squared = (world.t() * direction).magnitude_squared();
+        pdf_area * distance_squared / cosine(direction, &world.normal())
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        let towards = self
+            .object
+            .random(&transform_point(&self.to_object, origin));
+        self.to_world.transform_vector(&towards)
+    }
+
+    // exact where the surface grows by the same factor everywhere: when it's flat, or when the
+    // transform keeps shapes. sampling goes by pdf_surface, which doesn't rely on that
+    fn area(&self) -> f64 {
+        match self.object.sample_surface() {
+            Some(rec) => self.object.area() * self.area_scale(&rec.normal()),
+            None => 0.,
+        }
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        Some(self.to_world_hit(self.object.sample_surface()?))
+    }
+
+    // the object's density, spread over however much its surface grew there. stretching
+    // unevenly grows some parts of a curved surface more than others, so it's not uniform
+    fn pdf_surface(&self, rec: &HitRecord) -> f64 {
+        let rec = self.to_object_hit(rec.clone());
+        self.object.pdf_surface(&rec) / self.area_scale(&rec.normal())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{AaRect, Sphere};
+    use crate::material::{DiffuseLight, Material};
+    use std::f64::consts::PI;
+
+    fn light() -> Arc<dyn Material> {
+        Arc::new(DiffuseLight::from_color(Color::new(1., 1., 1.)))
+    }
+
+    // a transformed shape has to sample like the same shape made in place
+    fn assert_same_light(transformed: &Transform, direct: &dyn Hittable, origin: Point3) {
+        assert!(transformed.is_emissive());
+        assert!((transformed.area() - direct.area()).abs() < 1e-9 * direct.area());
+        for _ in 0..100 {
+            let towards = transformed.random(&origin);
+            let expected = direct.pdf_value(&origin, &towards);
+            let pdf = transformed.pdf_value(&origin, &towards);
+            assert!(expected > 0.);
+            assert!(
+                (pdf - expected).abs() < 1e-6 * expected,
+                "{} {}",
+                pdf,
+                expected
+            );
+
+            let rec = transformed.sample_surface().unwrap();
+            let expected = 1. / direct.area();
+            assert!((transformed.pdf_surface(&rec) - expected).abs() < 1e-9 * expected);
+            let back = Ray::new(
+                rec.p() + rec.normal().into_inner(),
+                -rec.normal().into_inner(),
+                0.,
+            );
+            let hit = direct.hit(&back, 0.5, 1.5).unwrap();
+            assert!((hit.p() - rec.p()).magnitude() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn samples_a_moved_sphere() {
+        let sphere = Arc::new(Sphere::new(Point3::zeros(), 1., light()));
+        let matrix = Matrix4::new_translation(&Vec3::new(1., 2., 3.)) * Matrix4::new_scaling(2.);
+        let transformed = Transform::new(sphere, matrix);
+        let direct = Sphere::new(Point3::new(1., 2., 3.), 2., light());
+        assert_same_light(&transformed, &direct, Point3::new(-5., 1., 0.));
+    }
+
+    #[test]
+    fn samples_a_stretched_rect() {
+        let rect = Arc::new(AaRect::xz((0., 1.), (0., 1.), 0., light()));
+        let matrix = Matrix4::new_translation(&Vec3::new(0., 5., 0.))
+            * Matrix4::new_nonuniform_scaling(&Vec3::new(2., 1., 3.));
+        let transformed = Transform::new(rect, matrix);
+        let direct = AaRect::xz((0., 2.), (0., 3.), 5., light());
+        assert_same_light(&transformed, &direct, Point3::new(0.5, 1., 2.));
+    }
+
+    #[test]
+    fn samples_a_squashed_sphere() {
+        // picking points by pdf_surface covers the whole surface of the spheroid, so the mean of
+        // 1 / pdf_surface is its area
+        let sphere = Arc::new(Sphere::new(Point3::zeros(), 1., light()));
+        let matrix = Matrix4::new_nonuniform_scaling(&Vec3::new(1., 0.5, 1.));
+        let transformed = Transform::new(sphere, matrix);
+        let n = 20_000;
+        let mean = (0..n)
+            .map(|_| 1. / transformed.pdf_surface(&transformed.sample_surface().unwrap()))
+            .sum::<f64>()
+            / n as f64;
+        let e = 0.75_f64.sqrt();
+        let expected = 2. * PI * (1. + 0.25 / e * e.atanh());
+        assert!(
+            (mean - expected).abs() < 0.02 * expected,
+            "{} {}",
+            mean,
+            expected
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use crate::hittable::{aabb::Aabb, hittable_list::HittableList, HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler;
+use crate::vec3::*;
+
+use std::sync::Arc;
+
+// vertex, normal and uv buffers shared by all the triangles of a mesh.
+// triangles are counter-clockwise when seen from the outside
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Option<Vec<Vec3>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    indices: Vec<[usize; 3]>,
+    material: Arc<dyn Material>,
+    // indexed like positions, blended across each face and handed to the material on the hit
+    colors: Option<Vec<Color>>,
+}
+
+impl TriangleMesh {
+    // normals and uvs, if present, are indexed like positions
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Option<Vec<Vec3>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        indices: Vec<[usize; 3]>,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        assert!(indices.iter().flatten().all(|&i| i < positions.len()));
+        assert!(normals.as_ref().is_none_or(|n| n.len() == positions.len()));
+        assert!(uvs.as_ref().is_none_or(|uv| uv.len() == positions.len()));
+        Self {
+            positions,
+            normals,
+            uvs,
+            indices,
+            material,
+            colors: None,
+        }
+    }
+
+    // give every vertex a color, indexed like positions
+    pub fn with_colors(mut self, colors: Vec<Color>) -> Self {
+        assert_eq!(colors.len(), self.positions.len());
+        self.colors = Some(colors);
+        self
+    }
+
+    // one hittable per triangle, all pointing at this mesh's buffers
+    pub fn into_hittable_list(self) -> HittableList {
+        let mesh = Arc::new(self);
+        let objects = (0..mesh.indices.len())
+            .map(|i| {
+                Arc::new(Triangle {
+                    mesh: Arc::clone(&mesh),
+                    index: i,
+                }) as Arc<dyn Hittable>
+            })
+            .collect();
+        HittableList::new(objects)
+    }
+}
+
+pub struct Triangle {
+    mesh: Arc<TriangleMesh>,
+    index: usize,
+}
+
+impl Triangle {
+    // a standalone triangle, with barycentric uvs and a flat normal
+    pub fn new(p0: Point3, p1: Point3, p2: Point3, material: Arc<dyn Material>) -> Self {
+        let mesh = TriangleMesh::new(vec![p0, p1, p2], None, None, vec![[0, 1, 2]], material);
+        Self {
+            mesh: Arc::new(mesh),
+            index: 0,
+        }
+    }
+
+    fn vertices(&self) -> [usize; 3] {
+        self.mesh.indices[self.index]
+    }
+
+    fn random_point(&self) -> Point3 {
+        let [i0, i1, i2] = self.vertices();
+        // folding the unit square onto the triangle keeps it uniform
+        let (mut b1, mut b2) = (sampler::random(), sampler::random());
+        if b1 + b2 > 1. {
+            b1 = 1. - b1;
+            b2 = 1. - b2;
+        }
+        let p0 = self.mesh.positions[i0];
+        p0 + b1 * (self.mesh.positions[i1] - p0) + b2 * (self.mesh.positions[i2] - p0)
+    }
+}
+
+impl Hittable for Triangle {
+    // möller-trumbore
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let [i0, i1, i2] = self.vertices();
+        let p0 = self.mesh.positions[i0];
+        let e1 = self.mesh.positions[i1] - p0;
+        let e2 = self.mesh.positions[i2] - p0;
+
+        let pvec = r.direction().cross(&e2);
+        let det = e1.dot(&pvec);
+        // relative to the sizes involved, so tiny and huge triangles both hit
+        if det.abs() < 1e-12 * e1.magnitude() * e2.magnitude() * r.direction().magnitude() {
+            // ray is parallel to the triangle, or the triangle is degenerate
+            return None;
+        }
+        let inv_det = 1. / det;
+
+        let tvec = r.origin() - p0;
+        let b1 = tvec.dot(&pvec) * inv_det;
+        if !(0. ..=1.).contains(&b1) {
+            return None;
+        }
+        let qvec = tvec.cross(&e1);
+        let b2 = r.direction().dot(&qvec) * inv_det;
+        if b2 < 0. || b1 + b2 > 1. {
+            return None;
+        }
+        let t = e2.dot(&qvec) * inv_det;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        let b0 = 1. - b1 - b2;
+
+        // vertex normals, when present, also decide which side is outside
+        let outward_normal = match &self.mesh.normals {
+            Some(ns) => Unit::new_normalize(b0 * ns[i0] + b1 * ns[i1] + b2 * ns[i2]),
+            None => Unit::new_normalize(e1.cross(&e2)),
+        };
+
+        let (u, v) = match &self.mesh.uvs {
+            Some(uvs) => (
+                b0 * uvs[i0].0 + b1 * uvs[i1].0 + b2 * uvs[i2].0,
+                b0 * uvs[i0].1 + b1 * uvs[i1].1 + b2 * uvs[i2].1,
+            ),
+            None => (b1, b2),
+        };
+
+        let rec = HitRecord::from_ray_outward_normal_material(
+            r.at(t),
+            t,
+            r,
+            outward_normal,
+            u,
+            v,
+            Arc::clone(&self.mesh.material),
+        );
+        Some(match &self.mesh.colors {
+            Some(cs) => rec.with_color(b0 * cs[i0] + b1 * cs[i1] + b2 * cs[i2]),
+            None => rec,
+        })
+    }
+
+    // uniform over the area, converted to solid angle
+    fn pdf_value(&self, origin: &Point3, direction: &Vec3) -> f64 {
+        let ray = Ray::new(*origin, *direction, 0.);
+        if self.hit(&ray, 0.001, f64::INFINITY).is_none() {
+            return 0.;
+        }
+        let [i0, i1, i2] = self.vertices();
+        let p0 = self.mesh.positions[i0];
+        let cross = (self.mesh.positions[i1] - p0).cross(&(self.mesh.positions[i2] - p0));
+        // the hit's t, from the plane, saves keeping the hit around
+        let t = (p0 - origin).dot(&cross) / direction.dot(&cross);
+        let distance_squared = t * t * direction.magnitude_squared();
+        let cosine = direction.dot(&cross).abs() / (direction.magnitude() * cross.magnitude());
+        distance_squared / (cosine * 0.5 * cross.magnitude())
+    }
+
+    fn random(&self, origin: &Point3) -> Vec3 {
+        self.random_point() - origin
+    }
+
+    fn is_emissive(&self) -> bool {
+        self.mesh.material.is_emissive()
+    }
+
+    fn area(&self) -> f64 {
+        let [i0, i1, i2] = self.vertices();
+        let p0 = self.mesh.positions[i0];
+        let cross = (self.mesh.positions[i1] - p0).cross(&(self.mesh.positions[i2] - p0));
+        0.5 * cross.magnitude()
+    }
+
+    fn sample_surface(&self) -> Option<HitRecord> {
+        let [i0, i1, i2] = self.vertices();
+        let p0 = self.mesh.positions[i0];
+        let normal = (self.mesh.positions[i1] - p0)
+            .cross(&(self.mesh.positions[i2] - p0))
+            .try_normalize(0.)?;
+        let p = self.random_point();
+        self.hit(&Ray::new(p + normal, -normal, 0.), 0.5, 1.5)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        let [i0, i1, i2] = self.vertices();
+        let p0 = self.mesh.positions[i0];
+        let bbox = Aabb::new(p0, p0)
+            .surrounding_point(&self.mesh.positions[i1])
+            .surrounding_point(&self.mesh.positions[i2]);
+        Some(bbox.pad(1e-4))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Lambertian;
+
+    fn material() -> Arc<dyn Material> {
+        Arc::new(Lambertian::from_color(Color::new(0.5, 0.5, 0.5)))
+    }
+
+    fn unit_triangle() -> Triangle {
+        Triangle::new(
+            Point3::new(0., 0., 0.),
+            Point3::new(1., 0., 0.),
+            Point3::new(0., 1., 0.),
+            material(),
+        )
+    }
+
+    fn down_at(x: f64, y: f64) -> Ray {
+        Ray::new(Point3::new(x, y, 1.), Vec3::new(0., 0., -1.), 0.)
+    }
+
+    #[test]
+    fn hit_inside() {
+        let rec = unit_triangle()
+            .hit(&down_at(0.25, 0.5), 0.001, f64::INFINITY)
+            .unwrap();
+        assert!((rec.t() - 1.).abs() < 1e-12);
+        assert!((rec.p() - Point3::new(0.25, 0.5, 0.)).magnitude() < 1e-12);
+        // counter-clockwise seen from +z, so that's the outside
+        assert!(rec.front_face());
+        assert!((rec.normal().into_inner() - Vec3::z()).magnitude() < 1e-12);
+        // no uvs of its own, so the barycentrics of the second and third vertices
+        assert!((rec.u() - 0.25).abs() < 1e-12);
+        assert!((rec.v() - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn hit_from_behind() {
+        let r = Ray::new(Point3::new(0.2, 0.2, -1.), Vec3::z(), 0.);
+        let rec = unit_triangle().hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!(!rec.front_face());
+        assert!((rec.normal().into_inner() + Vec3::z()).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn hit_tiny() {
+        // a micron across, with a short ray; an absolute threshold on det would call it parallel
+        let triangle = Triangle::new(
+            Point3::new(0., 0., 0.),
+            Point3::new(1e-6, 0., 0.),
+            Point3::new(0., 1e-6, 0.),
+            material(),
+        );
+        let r = Ray::new(
+            Point3::new(2.5e-7, 5e-7, 1e-6),
+            Vec3::new(0., 0., -1e-6),
+            0.,
+        );
+        let rec = triangle.hit(&r, 0.001, f64::INFINITY).unwrap();
+        assert!((rec.t() - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn misses() {
+        let triangle = unit_triangle();
+        // past the hypotenuse, and beside each of the other edges
+        for (x, y) in [(0.6, 0.6), (-0.1, 0.5), (0.5, -0.1)] {
+            assert!(triangle.hit(&down_at(x, y), 0.001, f64::INFINITY).is_none());
+        }
+        // parallel to the plane
+        let r = Ray::new(Point3::new(-1., 0.2, 0.), Vec3::x(), 0.);
+        assert!(triangle.hit(&r, 0.001, f64::INFINITY).is_none());
+        // outside of the t range
+        assert!(triangle.hit(&down_at(0.2, 0.2), 0.001, 0.5).is_none());
+        assert!(triangle.hit(&down_at(0.2, 0.2), 1.5, 2.).is_none());
+        // pointing away
+        let r = Ray::new(Point3::new(0.2, 0.2, 1.), Vec3::z(), 0.);
+        assert!(triangle.hit(&r, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn interpolates_mesh_uvs_and_normals() {
+        let positions = vec![
+            Point3::new(0., 0., 0.),
+            Point3::new(1., 0., 0.),
+            Point3::new(0., 1., 0.),
+        ];
+        let normals = vec![Vec3::z(), Vec3::z(), Vec3::new(0., 1., 1.)];
+        let uvs = vec![(0., 0.), (1., 0.), (0.5, 1.)];
+        let mesh = TriangleMesh::new(
+            positions,
+            Some(normals),
+            Some(uvs),
+            vec![[0, 1, 2]],
+            material(),
+        );
+        let list = mesh.into_hittable_list();
+        let rec = list.hit(&down_at(0.25, 0.5), 0.001, f64::INFINITY).unwrap();
+        // barycentrics 0.25, 0.25, 0.5
+        assert!((rec.u() - (0.25 + 0.25)).abs() < 1e-12);
+        assert!((rec.v() - 0.5).abs() < 1e-12);
+        let normal = Vec3::new(0., 0.5, 1.).normalize();
+        assert!((rec.normal().into_inner() - normal).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn area_and_samples() {
+        let triangle = unit_triangle();
+        assert!((triangle.area() - 0.5).abs() < 1e-12);
+        for _ in 0..100 {
+            let rec = triangle.sample_surface().unwrap();
+            let p = rec.p();
+            assert!(p.x >= 0. && p.y >= 0. && p.x + p.y <= 1. + 1e-12 && p.z.abs() < 1e-12);
+        }
+    }
+}
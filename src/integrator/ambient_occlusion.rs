@@ -0,0 +1,34 @@
+use crate::integrator::{Integrator, World};
+use crate::ray::Ray;
+use crate::vec3::{self, Color};
+
+// white where nothing is within radius over a surface, darker the more of its hemisphere is
+// blocked; materials and lights are ignored
+pub struct AmbientOcclusion {
+    radius: f64,
+}
+
+impl AmbientOcclusion {
+    pub fn new(radius: f64) -> Self {
+        Self { radius }
+    }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn ray_color(&self, r: &Ray, world: &World) -> Color {
+        let rec = match world.hit(r) {
+            Some(rec) => rec,
+            None => return Color::new(1., 1., 1.),
+        };
+        // cosine weighted, like a lambertian surface
+        let mut direction = rec.normal().into_inner() + vec3::random_unit().into_inner();
+        if vec3::near_zero(&direction) {
+            direction = rec.normal().into_inner();
+        }
+        let probe = Ray::new(rec.p(), direction.normalize(), r.time());
+        match world.objects.hit(&probe, 0.001, self.radius) {
+            Some(_) => Color::new(0., 0., 0.),
+            None => Color::new(1., 1., 1.),
+        }
+    }
+}
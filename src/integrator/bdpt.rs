@@ -0,0 +1,479 @@
+use crate::camera::Camera;
+use crate::hittable::HitRecord;
+use crate::integrator::{sample_emission, Integrator, World};
+use crate::output::Splats;
+use crate::ray::Ray;
+use crate::sampler;
+use crate::sampling::Heuristic;
+use crate::vec3::{self, Color, Point3, Unit, Vec3};
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// bidirectional path tracing: a path from the camera and one from a point on a light, joined
+// at every pair of their vertices, with each way of making the same path weighed against the
+// others. joins straight to the camera land on whatever pixel they do, as splats. only the
+// sampled lights start light paths; the environment and any other emitters are only found by
+// camera paths
+pub struct Bdpt {
+    camera: Camera,
+    width: u32,
+    height: u32,
+    splats: Arc<Splats>,
+    // of the part of the focus plane that camera rays go through, moved to distance 1
+    film_area: f64,
+    min_depth: u32,
+    max_depth: u32,
+    heuristic: Heuristic,
+}
+
+enum Kind {
+    Camera,
+    // picked on a light, to start a light path
+    Light,
+    // ray_in: the ray that arrived there
+    Surface { rec: HitRecord, ray_in: Ray },
+}
+
+struct Vertex {
+    kind: Kind,
+    p: Point3,
+    // none at the camera and in volumes, where there's no cosine term
+    normal: Option<Unit<Vec3>>,
+    // what reaches here of the light or importance the path started with, over its pdf
+    beta: Color,
+    // only scatters into a single direction, so it can't be joined to
+    delta: bool,
+    // pdfs with respect to area of this vertex being picked from the one before it along the
+    // path, and from the one after it if the path had been made the other way
+    pdf_fwd: f64,
+    pdf_rev: f64,
+}
+
+impl Vertex {
+    fn camera(p: Point3, beta: Color) -> Self {
+        Self {
+            kind: Kind::Camera,
+            p,
+            normal: None,
+            beta,
+            delta: false,
+            pdf_fwd: 1.,
+            pdf_rev: 0.,
+        }
+    }
+
+    // pdf: of picking the point, with respect to area
+    fn light(rec: &HitRecord, pdf: f64) -> Self {
+        let emitted = rec.material().emitted(rec);
+        Self {
+            kind: Kind::Light,
+            p: rec.p(),
+            normal: Some(rec.normal()),
+            beta: emitted / pdf,
+            delta: false,
+            pdf_fwd: pdf,
+            pdf_rev: 0.,
+        }
+    }
+
+    fn light_rec(&self) -> Option<(usize, &HitRecord)> {
+        match &self.kind {
+            Kind::Surface { rec, .. } => Some((rec.light()?, rec)),
+            _ => None,
+        }
+    }
+
+    // light given off here, if it's on an emitter
+    fn emitted(&self) -> Color {
+        match &self.kind {
+            Kind::Surface { rec, .. } => rec.material().emitted(rec),
+            _ => Color::new(0., 0., 0.),
+        }
+    }
+
+    // the brdf times the cosine term, going on towards p
+    fn bsdf(&self, p: &Point3) -> Option<Color> {
+        match &self.kind {
+            Kind::Surface { rec, ray_in } => {
+                let scattered = Ray::new(self.p, p - self.p, ray_in.time());
+                rec.material().bsdf(ray_in, rec, &scattered)
+            }
+            _ => None,
+        }
+    }
+
+    // a pdf with respect to solid angle here, converted to one with respect to area at next
+    fn to_area(&self, pdf: f64, next: &Vertex) -> f64 {
+        to_area(pdf, &self.p, &next.p, next.normal)
+    }
+
+    // of a light path leaving this point on a light towards next, with respect to area there
+    fn pdf_emission(&self, next: &Vertex) -> f64 {
+        let direction = (next.p - self.p).normalize();
+        let cosine = self.normal.map_or(0., |n| n.dot(&direction).abs());
+        self.to_area(cosine / (2. * PI), next)
+    }
+}
+
+fn to_area(pdf: f64, from: &Point3, to: &Point3, normal: Option<Unit<Vec3>>) -> f64 {
+    let w = to - from;
+    let distance_squared = w.magnitude_squared();
+    if distance_squared == 0. {
+        return 0.;
+    }
+    let cosine = normal.map_or(1., |n| n.dot(&w).abs() / distance_squared.sqrt());
+    pdf * cosine / distance_squared
+}
+
+fn visible(world: &World, from: &Point3, to: &Point3, time: f64) -> bool {
+    let w = to - from;
+    let distance = w.magnitude();
+    let ray = Ray::new(*from, w / distance, time);
+    world.objects.hit(&ray, 0.001, distance - 0.001).is_none()
+}
+
+fn is_black(c: &Color) -> bool {
+    c.iter().all(|&x| x == 0.)
+}
+
+impl Bdpt {
+    // width and height: of the image, to find the pixels splats land on
+    pub fn new(
+        camera: Camera,
+        width: u32,
+        height: u32,
+        splats: Arc<Splats>,
+        min_depth: u32,
+        max_depth: u32,
+        heuristic: Heuristic,
+    ) -> Self {
+        // main spreads the pixels over the viewport so that the last ones lie just past it
+        let film_area =
+            camera.viewport_area() * (width * height) as f64 / ((width - 1) * (height - 1)) as f64;
+        Self {
+            camera,
+            width,
+            height,
+            splats,
+            film_area,
+            min_depth,
+            max_depth,
+            heuristic,
+        }
+    }
+
+    // where on the image p shows up through a point on the lens
+    fn pixel(&self, lens: &Point3, p: &Point3) -> Option<(u32, u32)> {
+        let (s, t) = self.camera.project(lens, p)?;
+        let i = (s * (self.width - 1) as f64).floor();
+        let j = (t * (self.height - 1) as f64).floor();
+        if i < 0. || j < 0. || i >= self.width as f64 || j >= self.height as f64 {
+            return None;
+        }
+        // the image is upside down, as in main
+        Some((i as u32, self.height - 1 - j as u32))
+    }
+
+    // of a camera ray from a point on the lens going along direction, with respect to solid
+    // angle. the pixels are all sampled alike, so it's uniform over the film
+    fn pdf_camera(&self, lens: &Point3, direction: &Vec3) -> f64 {
+        if self.pixel(lens, &(lens + direction)).is_none() {
+            return 0.;
+        }
+        let cosine = direction.normalize().dot(&self.camera.forward());
+        1. / (self.film_area * cosine.powi(3))
+    }
+
+    // of v picking next, having come from prev, with respect to area at next
+    fn pdf(&self, v: &Vertex, prev: Option<&Vertex>, next: &Vertex) -> f64 {
+        let pdf = match (&v.kind, prev) {
+            (Kind::Camera, _) => self.pdf_camera(&v.p, &(next.p - v.p)),
+            (Kind::Light, _) => return v.pdf_emission(next),
+            (Kind::Surface { rec, ray_in }, Some(prev)) => {
+                let ray_in = Ray::new(prev.p, v.p - prev.p, ray_in.time());
+                let scattered = Ray::new(v.p, next.p - v.p, ray_in.time());
+                rec.material()
+                    .scattering_pdf(&ray_in, rec, &scattered)
+                    .unwrap_or(0.)
+            }
+            (Kind::Surface { .. }, None) => 0.,
+        };
+        v.to_area(pdf, next)
+    }
+
+    // adds vertices to path where ray and the rays scattered after it hit, until one leaves the
+    // scene or is absorbed, or there are max_vertices. pdf: of ray's direction. returns the
+    // throughput and the ray that left the scene, if one did
+    fn walk(
+        &self,
+        world: &World,
+        mut ray: Ray,
+        mut beta: Color,
+        mut pdf: f64,
+        max_vertices: usize,
+        path: &mut Vec<Vertex>,
+    ) -> Option<(Color, Ray)> {
+        while path.len() < max_vertices {
+            let rec = match world.hit(&ray) {
+                Some(rec) => rec,
+                None => return Some((beta, ray)),
+            };
+            let material = rec.material();
+            let p = rec.p();
+            let normal = if material.is_surface() {
+                Some(rec.normal())
+            } else {
+                None
+            };
+            let pdf_fwd = to_area(pdf, &path[path.len() - 1].p, &p, normal);
+
+            let mut delta = false;
+            let mut next = None;
+            if path.len() + 1 < max_vertices {
+                if let Some((scattered, attenuation)) = material.scatter(&ray, &rec) {
+                    // and of going the other way, back towards where ray came from
+                    let pdf_rev = match material.scattering_pdf(&ray, &rec, &scattered) {
+                        Some(pdf_scattered) => {
+                            pdf = pdf_scattered;
+                            let reversed = Ray::new(
+                                p + scattered.direction(),
+                                -scattered.direction(),
+                                ray.time(),
+                            );
+                            let back = Ray::new(p, -ray.direction(), ray.time());
+                            material
+                                .scattering_pdf(&reversed, &rec, &back)
+                                .unwrap_or(0.)
+                        }
+                        None => {
+                            delta = true;
+                            pdf = 0.;
+                            0.
+                        }
+                    };
+                    next = Some((scattered, attenuation, pdf_rev));
+                }
+            }
+
+            path.push(Vertex {
+                kind: Kind::Surface { rec, ray_in: ray },
+                p,
+                normal,
+                beta,
+                delta,
+                pdf_fwd,
+                pdf_rev: 0.,
+            });
+            let (scattered, attenuation, pdf_rev) = next?;
+            let n = path.len();
+            path[n - 2].pdf_rev = path[n - 1].to_area(pdf_rev, &path[n - 2]);
+            beta = vec3::mul_elemwise(&beta, &attenuation);
+
+            // russian roulette, as in the path integrator
+            if path.len() > self.min_depth as usize {
+                let survival = beta.max().min(0.95);
+                if sampler::random() >= survival {
+                    break;
+                }
+                beta /= survival;
+            }
+            ray = scattered;
+        }
+        None
+    }
+
+    // how much of a path made by joining the first s vertices of a light path to the first t
+    // of a camera path to count, against all the other ways of making it
+    fn mis_weight(&self, world: &World, light: &[&Vertex], camera: &[&Vertex]) -> f64 {
+        let (s, t) = (light.len(), camera.len());
+        if s + t == 2 {
+            return 1.;
+        }
+
+        // (pdf_fwd, pdf_rev, delta) of each vertex, with the ends joined
+        let mut light_pdfs: Vec<_> = light
+            .iter()
+            .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+            .collect();
+        let mut camera_pdfs: Vec<_> = camera
+            .iter()
+            .map(|v| (v.pdf_fwd, v.pdf_rev, v.delta))
+            .collect();
+        let pt = camera[t - 1];
+        let pt_minus = if t > 1 { Some(camera[t - 2]) } else { None };
+        camera_pdfs[t - 1].2 = false;
+        if s > 0 {
+            let qs = light[s - 1];
+            let qs_minus = if s > 1 { Some(light[s - 2]) } else { None };
+            light_pdfs[s - 1].2 = false;
+            camera_pdfs[t - 1].1 = self.pdf(qs, qs_minus, pt);
+            if let Some(pt_minus) = pt_minus {
+                camera_pdfs[t - 2].1 = self.pdf(pt, Some(qs), pt_minus);
+            }
+            light_pdfs[s - 1].1 = self.pdf(pt, pt_minus, qs);
+            if let Some(qs_minus) = qs_minus {
+                light_pdfs[s - 2].1 = self.pdf(qs, Some(pt), qs_minus);
+            }
+        } else if let Some((i, rec)) = pt.light_rec() {
+            // pt is on a light, which a light path could have started from
+            camera_pdfs[t - 1].1 = world.lights.pdf_point(i, rec);
+            if let Some(pt_minus) = pt_minus {
+                camera_pdfs[t - 2].1 = pt.pdf_emission(pt_minus);
+            }
+        }
+
+        // sum up the other strategies' pdfs over this one's, walking out from the join.
+        // delta vertices' pdfs are 0 on both sides, so they cancel out
+        let remap = |pdf: f64| if pdf != 0. { pdf } else { 1. };
+        let term = |ratio: f64| match self.heuristic {
+            Heuristic::Balance => ratio,
+            Heuristic::Power => ratio * ratio,
+        };
+        let mut sum = 0.;
+        let mut ratio = 1.;
+        for i in (1..t).rev() {
+            ratio *= remap(camera_pdfs[i].1) / remap(camera_pdfs[i].0);
+            if !camera_pdfs[i].2 && !camera_pdfs[i - 1].2 {
+                sum += term(ratio);
+            }
+        }
+        ratio = 1.;
+        for i in (0..s).rev() {
+            ratio *= remap(light_pdfs[i].1) / remap(light_pdfs[i].0);
+            let delta_before = i > 0 && light_pdfs[i - 1].2;
+            if !light_pdfs[i].2 && !delta_before {
+                sum += term(ratio);
+            }
+        }
+        1. / (1. + sum)
+    }
+
+    // the contribution of the (s, t) strategy; t = 1 ones are splatted instead
+    fn connect(
+        &self,
+        world: &World,
+        light: &[Vertex],
+        camera: &[Vertex],
+        s: usize,
+        t: usize,
+        time: f64,
+    ) -> Color {
+        let black = Color::new(0., 0., 0.);
+        let light_refs = || light[..s].iter().collect::<Vec<_>>();
+        let camera_refs = || camera[..t].iter().collect::<Vec<_>>();
+
+        // the camera path found a light by itself
+        if s == 0 {
+            let pt = &camera[t - 1];
+            let color = vec3::mul_elemwise(&pt.beta, &pt.emitted());
+            if is_black(&color) {
+                return black;
+            }
+            // lights that aren't sampled can't be found any other way
+            return match pt.light_rec() {
+                Some(_) => color * self.mis_weight(world, &[], &camera_refs()),
+                None => color,
+            };
+        }
+
+        // the light path joined straight to a point on the lens
+        if t == 1 {
+            let qs = &light[s - 1];
+            let lens = self.camera.sample_lens();
+            let ((x, y), bsdf) = match (self.pixel(&lens, &qs.p), qs.bsdf(&lens)) {
+                (Some(pixel), Some(bsdf)) => (pixel, bsdf),
+                _ => return black,
+            };
+            let w = qs.p - lens;
+            // the camera's importance over the pdf of picking the lens point
+            let importance = self.pdf_camera(&lens, &w) / w.magnitude_squared();
+            let color = vec3::mul_elemwise(&qs.beta, &bsdf) * importance;
+            if is_black(&color) || !visible(world, &lens, &qs.p, time) {
+                return black;
+            }
+            let sampled = Vertex::camera(lens, Color::from_element(importance));
+            let weight = self.mis_weight(world, &light_refs(), &[&sampled]);
+            self.splats.add(x, y, color * weight);
+            return black;
+        }
+
+        let pt = &camera[t - 1];
+
+        // the camera path joined to a new point on a light
+        if s == 1 {
+            let sampled = match world.lights.sample_point() {
+                Some((rec, pdf)) => Vertex::light(&rec, pdf),
+                None => return black,
+            };
+            let bsdf = match pt.bsdf(&sampled.p) {
+                Some(bsdf) => bsdf,
+                None => return black,
+            };
+            let w = sampled.p - pt.p;
+            let cosine = sampled.normal.map_or(0., |n| n.dot(&w.normalize()).abs());
+            let color = vec3::mul_elemwise(&vec3::mul_elemwise(&pt.beta, &bsdf), &sampled.beta)
+                * (cosine / w.magnitude_squared());
+            if is_black(&color) || !visible(world, &pt.p, &sampled.p, time) {
+                return black;
+            }
+            return color * self.mis_weight(world, &[&sampled], &camera_refs());
+        }
+
+        // the two paths joined in the middle
+        let qs = &light[s - 1];
+        let (qs_bsdf, pt_bsdf) = match (qs.bsdf(&pt.p), pt.bsdf(&qs.p)) {
+            (Some(qs_bsdf), Some(pt_bsdf)) => (qs_bsdf, pt_bsdf),
+            _ => return black,
+        };
+        let color = vec3::mul_elemwise(
+            &vec3::mul_elemwise(&qs.beta, &qs_bsdf),
+            &vec3::mul_elemwise(&pt_bsdf, &pt.beta),
+        ) / (qs.p - pt.p).magnitude_squared();
+        if is_black(&color) || !visible(world, &qs.p, &pt.p, time) {
+            return black;
+        }
+        color * self.mis_weight(world, &light_refs(), &camera_refs())
+    }
+}
+
+impl Integrator for Bdpt {
+    fn ray_color(&self, r: &Ray, world: &World) -> Color {
+        let time = r.time();
+        let max_depth = self.max_depth as usize;
+
+        let mut camera = vec![Vertex::camera(r.origin(), Color::new(1., 1., 1.))];
+        let pdf = self.pdf_camera(&r.origin(), &r.direction());
+        let escaped = self.walk(
+            world,
+            r.clone(),
+            Color::new(1., 1., 1.),
+            pdf,
+            max_depth + 2,
+            &mut camera,
+        );
+
+        let mut light = vec![];
+        if let Some((rec, pdf, ray, pdf_direction)) = sample_emission(world, time) {
+            let start = Vertex::light(&rec, pdf);
+            let cosine = rec.normal().dot(&ray.direction().normalize()).abs();
+            let beta = start.beta * (cosine / pdf_direction);
+            light.push(start);
+            self.walk(world, ray, beta, pdf_direction, max_depth + 1, &mut light);
+        }
+
+        let mut color = match escaped {
+            Some((beta, ray)) => vec3::mul_elemwise(&beta, &world.background.value(&ray)),
+            None => Color::new(0., 0., 0.),
+        };
+        for t in 1..=camera.len() {
+            for s in 0..=light.len() {
+                if (s == 1 && t == 1) || s + t < 2 || s + t - 2 > max_depth {
+                    continue;
+                }
+                color += self.connect(world, &light, &camera, s, t, time);
+            }
+        }
+        color
+    }
+}
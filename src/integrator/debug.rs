@@ -0,0 +1,83 @@
+#[cfg(feature = "heatmap")]
+use crate::hittable;
+use crate::integrator::{Integrator, World};
+use crate::ray::Ray;
+use crate::vec3::Color;
+
+// views of what the camera rays hit, for looking into a scene rather than lighting it. all
+// of them are black where nothing is hit
+
+// the shading normal, mapped from -1..1 to 0..1
+pub struct Normals;
+
+impl Integrator for Normals {
+    fn ray_color(&self, r: &Ray, world: &World) -> Color {
+        match world.hit(r) {
+            Some(rec) => 0.5 * (rec.normal().into_inner() + Color::new(1., 1., 1.)),
+            None => Color::new(0., 0., 0.),
+        }
+    }
+}
+
+// u as red, v as green
+pub struct Uvs;
+
+impl Integrator for Uvs {
+    fn ray_color(&self, r: &Ray, world: &World) -> Color {
+        match world.hit(r) {
+            Some(rec) => Color::new(rec.u(), rec.v(), 0.),
+            None => Color::new(0., 0., 0.),
+        }
+    }
+}
+
+// white up close, fading as exp(-distance / scale)
+pub struct Depth {
+    scale: f64,
+}
+
+impl Depth {
+    pub fn new(scale: f64) -> Self {
+        Self { scale }
+    }
+}
+
+impl Integrator for Depth {
+    fn ray_color(&self, r: &Ray, world: &World) -> Color {
+        match world.hit(r) {
+            Some(rec) => {
+                let distance = rec.t() * r.direction().magnitude();
+                Color::new(1., 1., 1.) * (-distance / self.scale).exp()
+            }
+            None => Color::new(0., 0., 0.),
+        }
+    }
+}
+
+// how many bounding boxes finding the first hit tested, from blue for none through green to
+// red for max or more. only built with the heatmap feature, which does the counting
+#[cfg(feature = "heatmap")]
+pub struct Heatmap {
+    max: f64,
+}
+
+#[cfg(feature = "heatmap")]
+impl Heatmap {
+    pub fn new(max: u64) -> Self {
+        Self { max: max as f64 }
+    }
+}
+
+#[cfg(feature = "heatmap")]
+impl Integrator for Heatmap {
+    fn ray_color(&self, r: &Ray, world: &World) -> Color {
+        hittable::take_box_tests();
+        world.hit(r);
+        let x = (hittable::take_box_tests() as f64 / self.max).min(1.);
+        if x < 0.5 {
+            Color::new(0., 2. * x, 1. - 2. * x)
+        } else {
+            Color::new(2. * x - 1., 2. - 2. * x, 0.)
+        }
+    }
+}
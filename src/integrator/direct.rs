@@ -0,0 +1,64 @@
+use crate::integrator::{background, emitted, sample_direct, samples_lights, Integrator, World};
+use crate::ray::Ray;
+use crate::sampling::Heuristic;
+use crate::vec3::{self, Color};
+
+// only light that reaches the first diffuse surface straight from an emitter or the
+// environment; mirrors and glass are still followed to get there
+pub struct Direct {
+    max_depth: u32,
+    heuristic: Heuristic,
+}
+
+impl Direct {
+    pub fn new(max_depth: u32, heuristic: Heuristic) -> Self {
+        Self {
+            max_depth,
+            heuristic,
+        }
+    }
+}
+
+impl Integrator for Direct {
+    fn ray_color(&self, r: &Ray, world: &World) -> Color {
+        let mut color = Color::new(0., 0., 0.);
+        let mut throughput = Color::new(1., 1., 1.);
+        let mut ray = r.clone();
+
+        for _ in 0..self.max_depth {
+            let rec = match world.hit(&ray) {
+                Some(rec) => rec,
+                None => {
+                    let light = background(&ray, world, self.heuristic, None);
+                    return color + vec3::mul_elemwise(&throughput, &light);
+                }
+            };
+            let light = emitted(&ray, &rec, world, self.heuristic, None);
+            color += vec3::mul_elemwise(&throughput, &light);
+            let material = rec.material();
+
+            if samples_lights(&ray, &rec, material.as_ref()) {
+                // the lights sampled, and the scattered ray in case it finds one of them itself
+                let mut light = sample_direct(&ray, &rec, material.as_ref(), world, self.heuristic);
+                if let Some((scattered, attenuation)) = material.scatter(&ray, &rec) {
+                    let pdf = material.scattering_pdf(&ray, &rec, &scattered);
+                    let found = match world.hit(&scattered) {
+                        Some(rec) => emitted(&scattered, &rec, world, self.heuristic, pdf),
+                        None => background(&scattered, world, self.heuristic, pdf),
+                    };
+                    light += vec3::mul_elemwise(&attenuation, &found);
+                }
+                return color + vec3::mul_elemwise(&throughput, &light);
+            }
+
+            // mirrors and glass
+            let (scattered, attenuation) = match material.scatter(&ray, &rec) {
+                Some(s) => s,
+                None => return color,
+            };
+            throughput = vec3::mul_elemwise(&throughput, &attenuation);
+            ray = scattered;
+        }
+        color
+    }
+}
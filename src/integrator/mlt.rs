@@ -0,0 +1,288 @@
+use crate::camera::Camera;
+use crate::integrator::{Integrator, Path, World};
+use crate::output::Splats;
+use crate::ray::Ray;
+use crate::sampler::{self, Sampler};
+use crate::sampling::Distribution1D;
+use crate::vec3::Color;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// primary sample space metropolis light transport, after kelemen et al. and pbrt. a path is
+// whatever the path integrator makes of a list of random numbers, so paths are changed by
+// changing the numbers, and they're kept or not in proportion to how bright they are. that
+// way, once a chain finds light getting through somewhere hard to reach, it stays around to
+// explore it. everything reaches the image as splats
+pub struct Mlt {
+    camera: Camera,
+    width: u32,
+    height: u32,
+    splats: Arc<Splats>,
+    path: Path,
+    // the image's average brightness, which the chains don't know by themselves
+    brightness: f64,
+    chains: Vec<Chain>,
+}
+
+// paths looked at to find the average brightness and where to start the chains
+const BOOTSTRAP: usize = 100_000;
+const CHAINS: usize = 1000;
+// how often a chain jumps to an all new path rather than a nearby one
+const LARGE_STEP_PROBABILITY: f64 = 0.3;
+// how far numbers move in a small step
+const SIGMA: f64 = 0.01;
+
+struct Chain {
+    sampler: MltSampler,
+    current: Sample,
+}
+
+// where on the image a path lands and what it brings
+struct Sample {
+    x: f64,
+    y: f64,
+    color: Color,
+}
+
+impl Sample {
+    fn luminance(&self) -> f64 {
+        0.2126 * self.color.x + 0.7152 * self.color.y + 0.0722 * self.color.z
+    }
+}
+
+impl Mlt {
+    pub fn new(camera: Camera, width: u32, height: u32, splats: Arc<Splats>, path: Path) -> Self {
+        Self {
+            camera,
+            width,
+            height,
+            splats,
+            path,
+            brightness: 0.,
+            chains: vec![],
+        }
+    }
+
+    // the path sampler's numbers make: the first two pick the point on the image, the rest
+    // go to the camera and the path integrator
+    fn evaluate(&self, world: &World, sampler: MltSampler) -> (MltSampler, Sample) {
+        sampler::with_sampler(sampler, || {
+            let x = sampler::random() * self.width as f64;
+            let y = sampler::random() * self.height as f64;
+            let r = self
+                .camera
+                .get_ray(x / (self.width - 1) as f64, y / (self.height - 1) as f64);
+            let color = self.path.ray_color(&r, world);
+            // paths that went wrong would stay forever
+            let color = if color.iter().all(|c| c.is_finite()) {
+                color
+            } else {
+                Color::zeros()
+            };
+            Sample { x, y, color }
+        })
+    }
+
+    fn splat(&self, sample: &Sample, weight: f64) {
+        // a path bringing no light has nothing to give, and can't be scaled by its brightness
+        if weight <= 0. || sample.luminance() <= 0. {
+            return;
+        }
+        let i = (sample.x as u32).min(self.width - 1);
+        let j = (sample.y as u32).min(self.height - 1);
+        // the image is upside down, as in main
+        let color = sample.color * (weight * self.brightness / sample.luminance());
+        self.splats.add(i, self.height - 1 - j, color);
+    }
+
+    // picks where the chains start from among a lot of paths, in proportion to how bright
+    // they are, and how bright the image is on average
+    fn bootstrap(&mut self, world: &World) {
+        let luminances = (0..BOOTSTRAP as u64)
+            .into_par_iter()
+            .map(|seed| self.evaluate(world, MltSampler::new(seed)).1.luminance())
+            .collect::<Vec<_>>();
+        let distribution = Distribution1D::new(luminances);
+        self.brightness = distribution.integral();
+        if self.brightness <= 0. {
+            return;
+        }
+
+        let mut rng = thread_rng();
+        let this = &*self;
+        let seeds = (0..CHAINS)
+            .map(|_| distribution.sample(rng.gen()).2 as u64)
+            .collect::<Vec<_>>();
+        self.chains = seeds
+            .into_par_iter()
+            .map(|seed| {
+                // the same numbers, so the same path again
+                let (sampler, current) = this.evaluate(world, MltSampler::new(seed));
+                Chain { sampler, current }
+            })
+            .collect();
+    }
+
+    fn mutate(&self, world: &World, chain: Chain, mutations: usize) -> Chain {
+        let Chain {
+            mut sampler,
+            mut current,
+        } = chain;
+        for _ in 0..mutations {
+            sampler.start_iteration();
+            let (next_sampler, proposed) = self.evaluate(world, sampler);
+            sampler = next_sampler;
+
+            let accept = if current.luminance() > 0. {
+                (proposed.luminance() / current.luminance()).min(1.)
+            } else {
+                1.
+            };
+            // both get their share, whichever the chain goes on with
+            self.splat(&proposed, accept);
+            self.splat(&current, 1. - accept);
+
+            if sampler.rng.gen::<f64>() < accept {
+                sampler.accept();
+                current = proposed;
+            } else {
+                sampler.reject();
+            }
+        }
+        Chain { sampler, current }
+    }
+}
+
+impl Integrator for Mlt {
+    // all the light arrives as splats
+    fn ray_color(&self, _r: &Ray, _world: &World) -> Color {
+        Color::zeros()
+    }
+
+    fn progressive(&self) -> bool {
+        true
+    }
+
+    fn splats_only(&self) -> bool {
+        true
+    }
+
+    // each pass makes as many mutations as there are pixels, spread over the chains
+    fn start_pass(&mut self, world: &World, pass: u32) {
+        if pass == 0 {
+            self.bootstrap(world);
+        }
+        let chains = std::mem::take(&mut self.chains);
+        let count = chains.len();
+        let pixels = self.width as usize * self.height as usize;
+        let this = &*self;
+        let chains = chains
+            .into_par_iter()
+            .enumerate()
+            .map(|(k, chain)| {
+                let mutations = pixels * (k + 1) / count - pixels * k / count;
+                this.mutate(world, chain, mutations)
+            })
+            .collect();
+        self.chains = chains;
+    }
+}
+
+// the numbers a path is made from. they're only worked out when the path asks for them, so
+// a number that's been left alone for a few small steps gets all of them at once; and they're
+// backed up, so a rejected path can be gone back to
+struct MltSampler {
+    rng: StdRng,
+    numbers: Vec<PrimarySample>,
+    iteration: u64,
+    last_large_step: u64,
+    large_step: bool,
+    index: usize,
+}
+
+struct PrimarySample {
+    value: f64,
+    last_modified: u64,
+    value_backup: f64,
+    modified_backup: u64,
+}
+
+impl MltSampler {
+    // the first path made from one is all new, and the same for the same seed
+    fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            numbers: vec![],
+            iteration: 0,
+            last_large_step: 0,
+            large_step: true,
+            index: 0,
+        }
+    }
+
+    fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.rng.gen::<f64>() < LARGE_STEP_PROBABILITY;
+        self.index = 0;
+    }
+
+    fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    fn reject(&mut self) {
+        for number in &mut self.numbers {
+            if number.last_modified == self.iteration {
+                number.value = number.value_backup;
+                number.last_modified = number.modified_backup;
+            }
+        }
+        self.iteration -= 1;
+    }
+}
+
+impl Sampler for MltSampler {
+    fn next(&mut self) -> f64 {
+        // a path that asks for more numbers than before gets new ones
+        if self.index >= self.numbers.len() {
+            let value = self.rng.gen();
+            self.numbers.push(PrimarySample {
+                value,
+                last_modified: self.iteration,
+                value_backup: value,
+                modified_backup: self.iteration,
+            });
+            self.index += 1;
+            return value;
+        }
+        let number = &mut self.numbers[self.index];
+        self.index += 1;
+
+        // a number the last large step didn't get to is as good as new
+        if number.last_modified < self.last_large_step {
+            number.value = self.rng.gen();
+            number.last_modified = self.last_large_step;
+        }
+        number.value_backup = number.value;
+        number.modified_backup = number.last_modified;
+
+        if self.large_step {
+            number.value = self.rng.gen();
+        } else {
+            // a normal step for each small step missed, which adds up to one wider one
+            let steps = (self.iteration - number.last_modified) as f64;
+            let (u1, u2): (f64, f64) = (self.rng.gen(), self.rng.gen());
+            let normal = (-2. * (1. - u1).ln()).sqrt() * (2. * PI * u2).cos();
+            number.value += normal * SIGMA * steps.sqrt();
+            number.value -= number.value.floor();
+        }
+        number.last_modified = self.iteration;
+        number.value
+    }
+}
@@ -0,0 +1,155 @@
+use crate::background::Background;
+use crate::hittable::{HitRecord, Hittable, Lights};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampler;
+use crate::sampling::Heuristic;
+use crate::vec3::{self, Color};
+
+use std::f64::consts::PI;
+
+mod ambient_occlusion;
+mod bdpt;
+mod debug;
+mod direct;
+mod mlt;
+mod path;
+mod photon_map;
+mod photon_mapping;
+
+pub use ambient_occlusion::AmbientOcclusion;
+pub use bdpt::Bdpt;
+#[cfg(feature = "heatmap")]
+pub use debug::Heatmap;
+pub use debug::{Depth, Normals, Uvs};
+pub use direct::Direct;
+pub use mlt::Mlt;
+pub use path::Path;
+pub use photon_mapping::PhotonMapping;
+
+// everything about the scene an integrator looks at
+pub struct World {
+    pub objects: Box<dyn Hittable>,
+    pub lights: Lights,
+    pub background: Background,
+}
+
+impl World {
+    pub fn hit(&self, r: &Ray) -> Option<HitRecord> {
+        self.objects.hit(r, 0.001, f64::INFINITY)
+    }
+}
+
+// how the light arriving at the camera is worked out
+pub trait Integrator: Send + Sync {
+    fn ray_color(&self, r: &Ray, world: &World) -> Color;
+
+    // whether the image is made in passes of one sample per pixel, with start_pass called
+    // before each, rather than taking all of a pixel's samples at once
+    fn progressive(&self) -> bool {
+        false
+    }
+
+    fn start_pass(&mut self, _world: &World, _pass: u32) {}
+
+    // whether all the light reaches the image as splats during start_pass, so there are no
+    // camera rays to trace and ray_color is never called
+    fn splats_only(&self) -> bool {
+        false
+    }
+}
+
+// a ray leaving a point picked on one of the lights. lights give off light from both sides, in
+// proportion to the cosine. -> the point, its pdf with respect to area, the ray, and the pdf of
+// its direction
+fn sample_emission(world: &World, time: f64) -> Option<(HitRecord, f64, Ray, f64)> {
+    let (rec, pdf) = world.lights.sample_point()?;
+    let side = if sampler::random() < 0.5 { 1. } else { -1. };
+    let normal = side * rec.normal().into_inner();
+    let mut direction = normal + vec3::random_unit().into_inner();
+    if vec3::near_zero(&direction) {
+        direction = normal;
+    }
+    let pdf_direction = normal.dot(&direction.normalize()) / (2. * PI);
+    let ray = Ray::new(rec.p(), direction, time);
+    Some((rec, pdf, ray, pdf_direction))
+}
+
+// light found by r leaving the scene. bsdf_pdf: the pdf of the bounce that sent r, if it
+// sampled the environment too, which this is then weighed against
+fn background(r: &Ray, world: &World, heuristic: Heuristic, bsdf_pdf: Option<f64>) -> Color {
+    let weight = match (bsdf_pdf, &world.background) {
+        (Some(pdf), Background::Environment(env)) => heuristic.weight(pdf, env.pdf(&r.direction())),
+        _ => 1.,
+    };
+    world.background.value(r) * weight
+}
+
+// light given off where r hit, likewise weighed against sampling the lights
+fn emitted(
+    r: &Ray,
+    rec: &HitRecord,
+    world: &World,
+    heuristic: Heuristic,
+    bsdf_pdf: Option<f64>,
+) -> Color {
+    let emitted = rec.material().emitted(rec);
+    match (bsdf_pdf, rec.light()) {
+        (Some(pdf), Some(i)) => {
+            emitted * heuristic.weight(pdf, world.lights.pdf(i, &r.origin(), &r.direction()))
+        }
+        _ => emitted,
+    }
+}
+
+// whether lights can be sampled at rec, which takes a scattering_pdf. materials that only
+// scatter into one direction have none for any direction, so any direction does to ask
+fn samples_lights(r: &Ray, rec: &HitRecord, material: &dyn Material) -> bool {
+    let any = Ray::new(rec.p(), rec.normal().into_inner(), r.time());
+    material.scattering_pdf(r, rec, &any).is_some()
+}
+
+// light scattered at rec towards r's origin, from a direction picked towards the bright parts
+// of the environment and from a point picked on one of the lights, rather than waiting for
+// scattered rays to find them. only for materials that samples_lights. it doesn't depend on
+// what scatter does, so it's taken whether or not scatter finds a direction
+fn sample_direct(
+    r: &Ray,
+    rec: &HitRecord,
+    material: &dyn Material,
+    world: &World,
+    heuristic: Heuristic,
+) -> Color {
+    let mut direct = Color::new(0., 0., 0.);
+    let mut add = |to_light: &Ray, light_pdf: f64, light: &dyn Fn() -> Option<Color>| {
+        let scattering_pdf = material.scattering_pdf(r, rec, to_light).unwrap_or(0.);
+        if scattering_pdf > 0. && light_pdf > 0. {
+            if let (Some(bsdf), Some(light)) = (material.bsdf(r, rec, to_light), light()) {
+                direct += vec3::mul_elemwise(&bsdf, &light)
+                    * (heuristic.weight(light_pdf, scattering_pdf) / light_pdf);
+            }
+        }
+    };
+
+    if let Background::Environment(env) = &world.background {
+        let (direction, light_pdf) = env.sample(sampler::random(), sampler::random());
+        let to_light = Ray::new(rec.p(), direction, r.time());
+        add(&to_light, light_pdf, &|| match world.hit(&to_light) {
+            Some(_) => None,
+            None => Some(env.value(&direction)),
+        });
+    }
+
+    // it only counts if nothing else is in the way
+    if !world.lights.is_empty() {
+        let (i, direction, light_pdf) = world.lights.sample(&rec.p());
+        let to_light = Ray::new(rec.p(), direction, r.time());
+        add(&to_light, light_pdf, &|| {
+            let light_rec = world.hit(&to_light).filter(|h| h.light() == Some(i))?;
+            let material = light_rec.material();
+            Some(material.emitted(&light_rec))
+        });
+    }
+
+    direct
+}
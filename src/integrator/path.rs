@@ -0,0 +1,73 @@
+use crate::integrator::{background, emitted, sample_direct, samples_lights, Integrator, World};
+use crate::ray::Ray;
+use crate::sampler;
+use crate::sampling::Heuristic;
+use crate::vec3::{self, Color};
+
+// follows rays as they bounce around, taking light straight from the lights at every bounce
+// that scatters diffusely. after min_depth bounces, paths carrying little light are ended at
+// random, and the ones that go on carry more to make up for it
+pub struct Path {
+    min_depth: u32,
+    max_depth: u32,
+    heuristic: Heuristic,
+}
+
+impl Path {
+    pub fn new(min_depth: u32, max_depth: u32, heuristic: Heuristic) -> Self {
+        Self {
+            min_depth,
+            max_depth,
+            heuristic,
+        }
+    }
+}
+
+impl Integrator for Path {
+    fn ray_color(&self, r: &Ray, world: &World) -> Color {
+        let mut color = Color::new(0., 0., 0.);
+        // how much of the light arriving along ray makes it to the camera
+        let mut throughput = Color::new(1., 1., 1.);
+        let mut ray = r.clone();
+        // the pdf of the bounce that sent ray, if it sampled the lights and the environment too
+        let mut bsdf_pdf = None;
+
+        for depth in 0..self.max_depth {
+            let rec = match world.hit(&ray) {
+                Some(rec) => rec,
+                None => {
+                    let light = background(&ray, world, self.heuristic, bsdf_pdf);
+                    color += vec3::mul_elemwise(&throughput, &light);
+                    break;
+                }
+            };
+            let light = emitted(&ray, &rec, world, self.heuristic, bsdf_pdf);
+            color += vec3::mul_elemwise(&throughput, &light);
+            let material = rec.material();
+
+            // only for materials that scatter over a spread of directions
+            if samples_lights(&ray, &rec, material.as_ref()) {
+                let light = sample_direct(&ray, &rec, material.as_ref(), world, self.heuristic);
+                color += vec3::mul_elemwise(&throughput, &light);
+            }
+
+            let (scattered, attenuation) = match material.scatter(&ray, &rec) {
+                Some(s) => s,
+                None => break,
+            };
+            bsdf_pdf = material.scattering_pdf(&ray, &rec, &scattered);
+            throughput = vec3::mul_elemwise(&throughput, &attenuation);
+
+            // russian roulette
+            if depth >= self.min_depth {
+                let survival = throughput.max().min(0.95);
+                if sampler::random() >= survival {
+                    break;
+                }
+                throughput /= survival;
+            }
+            ray = scattered;
+        }
+        color
+    }
+}
@@ -0,0 +1,79 @@
+use crate::vec3::{Color, Point3, Vec3};
+
+pub struct Photon {
+    pub p: Point3,
+    // towards where it came from
+    pub direction: Vec3,
+    pub power: Color,
+}
+
+// photons in a balanced kd-tree, laid out implicitly: each range of the array has its node in
+// the middle, split along axes[middle], with the two halves on either side of it
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> Self {
+        let mut axes = vec![0; photons.len()];
+        build(&mut photons, &mut axes);
+        Self { photons, axes }
+    }
+
+    // calls f with every photon within radius of p
+    pub fn for_each_near(&self, p: &Point3, radius: f64, mut f: impl FnMut(&Photon)) {
+        self.search(0, self.photons.len(), p, radius * radius, &mut f);
+    }
+
+    fn search(
+        &self,
+        start: usize,
+        end: usize,
+        p: &Point3,
+        radius_squared: f64,
+        f: &mut impl FnMut(&Photon),
+    ) {
+        if start >= end {
+            return;
+        }
+        let middle = (start + end) / 2;
+        let photon = &self.photons[middle];
+        if (photon.p - p).magnitude_squared() <= radius_squared {
+            f(photon);
+        }
+        // the side p is on first, then the other one if the sphere reaches over
+        let d = p[self.axes[middle]] - photon.p[self.axes[middle]];
+        let (near, far) = if d <= 0. {
+            ((start, middle), (middle + 1, end))
+        } else {
+            ((middle + 1, end), (start, middle))
+        };
+        self.search(near.0, near.1, p, radius_squared, f);
+        if d * d <= radius_squared {
+            self.search(far.0, far.1, p, radius_squared, f);
+        }
+    }
+}
+
+// splits along the longest axis of the photons' bounds, at the median
+fn build(photons: &mut [Photon], axes: &mut [usize]) {
+    if photons.is_empty() {
+        return;
+    }
+    let mut min = photons[0].p;
+    let mut max = photons[0].p;
+    for photon in photons.iter() {
+        min = min.inf(&photon.p);
+        max = max.sup(&photon.p);
+    }
+    let axis = (max - min).imax();
+
+    let middle = photons.len() / 2;
+    photons.select_nth_unstable_by(middle, |a, b| a.p[axis].total_cmp(&b.p[axis]));
+    axes[middle] = axis;
+    let (left, right) = photons.split_at_mut(middle);
+    let (left_axes, right_axes) = axes.split_at_mut(middle);
+    build(left, left_axes);
+    build(&mut right[1..], &mut right_axes[1..]);
+}
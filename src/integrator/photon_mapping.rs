@@ -0,0 +1,173 @@
+use crate::integrator::photon_map::{Photon, PhotonMap};
+use crate::integrator::{
+    background, emitted, sample_direct, sample_emission, samples_lights, Integrator, World,
+};
+use crate::ray::Ray;
+use crate::sampler;
+use crate::sampling::Heuristic;
+use crate::vec3::{self, Color};
+
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+// progressive photon mapping: each pass sends photons out from the sampled lights, keeps where
+// they land on diffuse surfaces after their first bounce, and estimates the light arriving
+// at the surfaces camera rays see from the photons around them. direct light is sampled as
+// in the direct integrator. the radius shrinks every pass, so that the estimate's blur and
+// noise both go away, after knaus and zwicker. light from the environment and from lights that
+// aren't sampled only arrives directly, and volumes only get direct light
+pub struct PhotonMapping {
+    photons_per_pass: usize,
+    radius: f64,
+    min_depth: u32,
+    max_depth: u32,
+    heuristic: Heuristic,
+    map: PhotonMap,
+}
+
+// how fast the radius shrinks; between 0 and 1, and smaller is faster
+const ALPHA: f64 = 2. / 3.;
+
+impl PhotonMapping {
+    pub fn new(
+        photons_per_pass: usize,
+        radius: f64,
+        min_depth: u32,
+        max_depth: u32,
+        heuristic: Heuristic,
+    ) -> Self {
+        Self {
+            photons_per_pass,
+            radius,
+            min_depth,
+            max_depth,
+            heuristic,
+            map: PhotonMap::new(vec![]),
+        }
+    }
+
+    // follows a photon from one of the lights, keeping where it lands after its first bounce
+    fn trace_photon(&self, world: &World, photons: &mut Vec<Photon>) {
+        let (rec, pdf, mut ray, pdf_direction) = match sample_emission(world, 0.) {
+            Some(s) => s,
+            None => return,
+        };
+        let cosine = rec.normal().dot(&ray.direction().normalize()).abs();
+        let emitted = rec.material().emitted(&rec);
+        let mut power = emitted * (cosine / (pdf * pdf_direction * self.photons_per_pass as f64));
+
+        for depth in 0..self.max_depth {
+            let rec = match world.hit(&ray) {
+                Some(rec) => rec,
+                None => break,
+            };
+            let material = rec.material();
+            let diffuse = samples_lights(&ray, &rec, material.as_ref());
+            if depth > 0 && diffuse && material.is_surface() {
+                photons.push(Photon {
+                    p: rec.p(),
+                    direction: -ray.direction().normalize(),
+                    power,
+                });
+            }
+
+            let (scattered, attenuation) = match material.scatter(&ray, &rec) {
+                Some(s) => s,
+                None => break,
+            };
+            power = vec3::mul_elemwise(&power, &attenuation);
+
+            // russian roulette, as in the path integrator
+            if depth >= self.min_depth {
+                // the power is split between all the photons, so undo that first
+                let survival = (power.max() * self.photons_per_pass as f64).min(0.95);
+                if sampler::random() >= survival {
+                    break;
+                }
+                power /= survival;
+            }
+            ray = scattered;
+        }
+    }
+}
+
+impl Integrator for PhotonMapping {
+    fn ray_color(&self, r: &Ray, world: &World) -> Color {
+        let mut color = Color::new(0., 0., 0.);
+        let mut throughput = Color::new(1., 1., 1.);
+        let mut ray = r.clone();
+
+        for _ in 0..self.max_depth {
+            let rec = match world.hit(&ray) {
+                Some(rec) => rec,
+                None => {
+                    let light = background(&ray, world, self.heuristic, None);
+                    return color + vec3::mul_elemwise(&throughput, &light);
+                }
+            };
+            let light = emitted(&ray, &rec, world, self.heuristic, None);
+            color += vec3::mul_elemwise(&throughput, &light);
+            let material = rec.material();
+
+            if samples_lights(&ray, &rec, material.as_ref()) {
+                // direct light, as in the direct integrator
+                let mut light = sample_direct(&ray, &rec, material.as_ref(), world, self.heuristic);
+                if let Some((scattered, attenuation)) = material.scatter(&ray, &rec) {
+                    let pdf = material.scattering_pdf(&ray, &rec, &scattered);
+                    let found = match world.hit(&scattered) {
+                        Some(rec) => emitted(&scattered, &rec, world, self.heuristic, pdf),
+                        None => background(&scattered, world, self.heuristic, pdf),
+                    };
+                    light += vec3::mul_elemwise(&attenuation, &found);
+                }
+
+                // and the rest from the photons around. the photons' power already has the
+                // cosine at the surface in it, so it comes off the bsdf
+                if material.is_surface() {
+                    let mut indirect = Color::new(0., 0., 0.);
+                    self.map.for_each_near(&rec.p(), self.radius, |photon| {
+                        let cosine = rec.normal().dot(&photon.direction);
+                        let towards = Ray::new(rec.p(), photon.direction, ray.time());
+                        if let (true, Some(bsdf)) =
+                            (cosine > 0., material.bsdf(&ray, &rec, &towards))
+                        {
+                            indirect += vec3::mul_elemwise(&bsdf, &photon.power) / cosine;
+                        }
+                    });
+                    light += indirect / (PI * self.radius * self.radius);
+                }
+                return color + vec3::mul_elemwise(&throughput, &light);
+            }
+
+            // mirrors and glass
+            let (scattered, attenuation) = match material.scatter(&ray, &rec) {
+                Some(s) => s,
+                None => return color,
+            };
+            throughput = vec3::mul_elemwise(&throughput, &attenuation);
+            ray = scattered;
+        }
+        color
+    }
+
+    fn progressive(&self) -> bool {
+        true
+    }
+
+    fn start_pass(&mut self, world: &World, pass: u32) {
+        if pass > 0 {
+            let i = pass as f64;
+            self.radius *= ((i + ALPHA) / (i + 1.)).sqrt();
+        }
+        let this = &*self;
+        let photons = (0..this.photons_per_pass)
+            .into_par_iter()
+            .fold(Vec::new, |mut photons, _| {
+                this.trace_photon(world, &mut photons);
+                photons
+            })
+            .flatten()
+            .collect();
+        self.map = PhotonMap::new(photons);
+    }
+}
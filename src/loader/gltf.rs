@@ -0,0 +1,291 @@
+use crate::camera::Camera;
+use crate::hittable::{HittableList, TriangleMesh};
+use crate::material::{self, Material};
+use crate::texture::{Filter, ImageTexture, Wrap};
+use crate::vec3::{Color, Point3, Vec3};
+
+use ::gltf::camera::Projection;
+use ::gltf::image::Format;
+use ::gltf::material::AlphaMode;
+use ::gltf::mesh::Mode;
+use ::gltf::texture::{MagFilter, WrappingMode};
+use anyhow::{anyhow, bail, Context, Result};
+use image::RgbImage;
+use nalgebra::{Matrix3, Matrix4, Vector4};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+// gltf 2.0 scenes (.gltf with external or embedded buffers, or .glb).
+// node transforms are baked into the mesh vertices, and pbr materials are mapped onto whichever
+// of our materials they most resemble.
+// https://www.khronos.org/registry/glTF/specs/2.0/glTF-2.0.html
+
+// the first perspective camera found in the scene is returned along with the world
+pub fn load_gltf<P: AsRef<Path>>(
+    path: P,
+    aspect_ratio: f64,
+) -> Result<(HittableList, Option<Camera>)> {
+    let path = path.as_ref();
+    let (document, buffers, images) =
+        ::gltf::import(path).with_context(|| format!("failed to load {}", path.display()))?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| anyhow!("{} has no scenes", path.display()))?;
+
+    let mut loader = Loader {
+        buffers: &buffers,
+        images: &images,
+        aspect_ratio,
+        materials: HashMap::new(),
+        world: HittableList::default(),
+        camera: None,
+    };
+    for node in scene.nodes() {
+        loader
+            .visit(&node, &Matrix4::identity())
+            .with_context(|| format!("failed to load {}", path.display()))?;
+    }
+    Ok((loader.world, loader.camera))
+}
+
+struct Loader<'a> {
+    buffers: &'a [::gltf::buffer::Data],
+    images: &'a [::gltf::image::Data],
+    aspect_ratio: f64,
+    // by material index; primitives without a material share the None entry
+    materials: HashMap<Option<usize>, Arc<dyn Material>>,
+    world: HittableList,
+    camera: Option<Camera>,
+}
+
+impl<'a> Loader<'a> {
+    fn visit(&mut self, node: &::gltf::Node, parent: &Matrix4<f64>) -> Result<()> {
+        let local = node.transform().matrix();
+        // gltf matrices are column-major
+        let transform = parent * Matrix4::from_fn(|r, c| local[c][r] as f64);
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.add_primitive(&primitive, &transform)
+                    .with_context(|| {
+                        format!(
+                            "mesh {}, primitive {}",
+                            mesh.name()
+                                .map_or_else(|| mesh.index().to_string(), str::to_string),
+                            primitive.index()
+                        )
+                    })?;
+            }
+        }
+
+        if let (None, Some(camera)) = (&self.camera, node.camera()) {
+            if let Projection::Perspective(p) = camera.projection() {
+                // gltf cameras look down -z with +y up
+                let eye = transform * Vector4::new(0., 0., 0., 1.);
+                let forward = transform * Vector4::new(0., 0., -1., 0.);
+                let up = transform * Vector4::new(0., 1., 0., 0.);
+                let lookfrom = Point3::new(eye.x, eye.y, eye.z);
+                self.camera = Some(Camera::new(
+                    lookfrom,
+                    lookfrom + Vec3::new(forward.x, forward.y, forward.z),
+                    Vec3::new(up.x, up.y, up.z),
+                    (p.yfov() as f64).to_degrees(),
+                    self.aspect_ratio,
+                    0.,
+                    1.,
+                ));
+            }
+        }
+
+        for child in node.children() {
+            self.visit(&child, &transform)?;
+        }
+        Ok(())
+    }
+
+    fn add_primitive(
+        &mut self,
+        primitive: &::gltf::Primitive,
+        transform: &Matrix4<f64>,
+    ) -> Result<()> {
+        let mode = primitive.mode();
+        if !matches!(
+            mode,
+            Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan
+        ) {
+            // points and lines have no surface to hit
+            return Ok(());
+        }
+
+        let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+        let linear = Matrix3::from_fn(|r, c| transform[(r, c)]);
+
+        let positions: Vec<Point3> = reader
+            .read_positions()
+            .ok_or_else(|| anyhow!("primitive has no positions"))?
+            .map(|[x, y, z]| {
+                let p = transform * Vector4::new(x as f64, y as f64, z as f64, 1.);
+                Point3::new(p.x, p.y, p.z)
+            })
+            .collect();
+        // normals transform by the inverse transpose; a singular transform has no sensible normals
+        let normals: Option<Vec<Vec3>> = reader.read_normals().and_then(|ns| {
+            let normal_matrix = linear.try_inverse()?.transpose();
+            Some(
+                ns.map(|[x, y, z]| normal_matrix * Vec3::new(x as f64, y as f64, z as f64))
+                    .collect(),
+            )
+        });
+        // gltf puts the uv origin at the top left of the image, we put it at the bottom left
+        let uvs: Option<Vec<(f64, f64)>> = reader.read_tex_coords(0).map(|tcs| {
+            tcs.into_f32()
+                .map(|[u, v]| (u as f64, 1. - v as f64))
+                .collect()
+        });
+        let vertex_indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|i| i as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+        if let Some(bad) = vertex_indices.iter().find(|&&i| i >= positions.len()) {
+            bail!("index {} out of range ({} vertices)", bad, positions.len());
+        }
+
+        let mut indices: Vec<[usize; 3]> = match mode {
+            Mode::Triangles => vertex_indices
+                .chunks_exact(3)
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            // every other strip triangle has reversed winding
+            Mode::TriangleStrip => (0..vertex_indices.len().saturating_sub(2))
+                .map(|i| {
+                    let t = &vertex_indices[i..i + 3];
+                    if i % 2 == 0 {
+                        [t[0], t[1], t[2]]
+                    } else {
+                        [t[1], t[0], t[2]]
+                    }
+                })
+                .collect(),
+            _ => (1..vertex_indices.len().saturating_sub(1))
+                .map(|i| [vertex_indices[0], vertex_indices[i], vertex_indices[i + 1]])
+                .collect(),
+        };
+        // a mirroring transform flips the winding, which decides the outside without normals
+        if normals.is_none() && linear.determinant() < 0. {
+            for t in indices.iter_mut() {
+                t.swap(1, 2);
+            }
+        }
+        if indices.is_empty() {
+            return Ok(());
+        }
+
+        let material = self.material(&primitive.material())?;
+        let mesh = TriangleMesh::new(positions, normals, uvs, indices, material);
+        for tri in mesh.into_hittable_list().into_inner() {
+            self.world.add(tri);
+        }
+        Ok(())
+    }
+
+    fn material(&mut self, m: &::gltf::Material) -> Result<Arc<dyn Material>> {
+        if let Some(cached) = self.materials.get(&m.index()) {
+            return Ok(Arc::clone(cached));
+        }
+
+        let pbr = m.pbr_metallic_roughness();
+        let [r, g, b, alpha] = pbr.base_color_factor();
+        let base_color = Color::new(r as f64, g as f64, b as f64);
+        let transmission = m.transmission().map_or(0., |t| t.transmission_factor());
+
+        let [er, eg, eb] = m.emissive_factor();
+        let emissive = Color::new(er as f64, eg as f64, eb as f64);
+
+        let material: Arc<dyn Material> = if emissive.max() > 0. {
+            // emissive textures aren't supported, only the factor
+            Arc::new(material::DiffuseLight::from_color(emissive))
+        } else if transmission > 0. || (m.alpha_mode() == AlphaMode::Blend && alpha < 1.) {
+            Arc::new(material::Dielectric::new(m.ior().unwrap_or(1.5) as f64))
+        } else if pbr.metallic_factor() >= 0.5 {
+            Arc::new(material::Metal::from_color(
+                base_color,
+                pbr.roughness_factor() as f64,
+            ))
+        } else if let Some(info) = pbr.base_color_texture() {
+            // the texture stands in for the base color; the factor is usually white anyway
+            let image = &self.images[info.texture().source().index()];
+            let sampler = info.texture().sampler();
+            let filter = match sampler.mag_filter() {
+                Some(MagFilter::Nearest) => Filter::Nearest,
+                _ => Filter::Bilinear,
+            };
+            let texture = ImageTexture::new(
+                to_rgb_image(image)
+                    .with_context(|| format!("base color texture {}", info.texture().index()))?,
+            )
+            .with_wrap(wrap(sampler.wrap_s()), wrap(sampler.wrap_t()))
+            .with_filter(filter);
+            Arc::new(material::Lambertian::new(Arc::new(texture)))
+        } else {
+            Arc::new(material::Lambertian::from_color(base_color))
+        };
+
+        self.materials.insert(m.index(), Arc::clone(&material));
+        Ok(material)
+    }
+}
+
+fn wrap(mode: WrappingMode) -> Wrap {
+    match mode {
+        WrappingMode::ClampToEdge => Wrap::Clamp,
+        WrappingMode::MirroredRepeat => Wrap::Mirror,
+        WrappingMode::Repeat => Wrap::Repeat,
+    }
+}
+
+fn to_rgb_image(image: &::gltf::image::Data) -> Result<RgbImage> {
+    let px = &image.pixels;
+    let mut rgb = Vec::with_capacity(image.width as usize * image.height as usize * 3);
+    // 16 bit channels come out in native byte order; keep the high byte
+    let high = |c: &[u8]| (u16::from_ne_bytes([c[0], c[1]]) >> 8) as u8;
+    match image.format {
+        // one and two channel images are luma, with or without alpha
+        Format::R8 => px.iter().for_each(|&l| rgb.extend_from_slice(&[l, l, l])),
+        Format::R8G8 => px
+            .chunks_exact(2)
+            .for_each(|c| rgb.extend_from_slice(&[c[0], c[0], c[0]])),
+        Format::R8G8B8 => rgb.extend_from_slice(px),
+        Format::R8G8B8A8 => px
+            .chunks_exact(4)
+            .for_each(|c| rgb.extend_from_slice(&c[..3])),
+        Format::B8G8R8 => px
+            .chunks_exact(3)
+            .for_each(|c| rgb.extend_from_slice(&[c[2], c[1], c[0]])),
+        Format::B8G8R8A8 => px
+            .chunks_exact(4)
+            .for_each(|c| rgb.extend_from_slice(&[c[2], c[1], c[0]])),
+        Format::R16 => px.chunks_exact(2).for_each(|c| {
+            let l = high(c);
+            rgb.extend_from_slice(&[l, l, l])
+        }),
+        Format::R16G16 => px.chunks_exact(4).for_each(|c| {
+            let l = high(&c[..2]);
+            rgb.extend_from_slice(&[l, l, l])
+        }),
+        Format::R16G16B16 => px
+            .chunks_exact(6)
+            .for_each(|c| rgb.extend_from_slice(&[high(&c[..2]), high(&c[2..4]), high(&c[4..6])])),
+        Format::R16G16B16A16 => px
+            .chunks_exact(8)
+            .for_each(|c| rgb.extend_from_slice(&[high(&c[..2]), high(&c[2..4]), high(&c[4..6])])),
+    }
+    RgbImage::from_raw(image.width, image.height, rgb).ok_or_else(|| {
+        anyhow!(
+            "image data doesn't match its {}x{} size",
+            image.width,
+            image.height
+        )
+    })
+}
@@ -0,0 +1,7 @@
+mod gltf;
+mod obj;
+mod ply;
+
+pub use crate::loader::gltf::load_gltf;
+pub use crate::loader::obj::load_obj;
+pub use crate::loader::ply::load_ply;
@@ -0,0 +1,536 @@
+use crate::hittable::{HittableList, TriangleMesh};
+use crate::material::{self, Material};
+use crate::texture::ImageTexture;
+use crate::vec3::{Color, Point3, Vec3};
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+// wavefront .obj meshes and their .mtl material libraries.
+// faces are fan-triangulated and grouped into one TriangleMesh per material.
+// http://paulbourke.net/dataformats/obj/ and http://paulbourke.net/dataformats/mtl/
+
+// (position, uv, normal) indices into the file's buffers, all 0-based
+type FaceVertex = (usize, Option<usize>, Option<usize>);
+
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<HittableList> {
+    let path = path.as_ref();
+    let src =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    parse_obj(&src, path)
+}
+
+// path is where src came from, for errors and to find material libraries next to it
+fn parse_obj(src: &str, path: &Path) -> Result<HittableList> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut positions: Vec<Point3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<(f64, f64)> = vec![];
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+    // triangles grouped by material, in order of first use. None is the default material
+    let mut groups: Vec<(Option<String>, Vec<[FaceVertex; 3]>)> = vec![(None, vec![])];
+    let mut current = 0;
+
+    for (i, line) in src.lines().enumerate() {
+        let at = || format!("{}:{}", path.display(), i + 1);
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => positions.push(parse_vec3(&args).with_context(at)?),
+            "vn" => normals.push(parse_vec3(&args).with_context(at)?),
+            "vt" => {
+                let uv = match args.as_slice() {
+                    [u] => (parse_num(u), Ok(0.)),
+                    [u, v, ..] => (parse_num(u), parse_num(v)),
+                    _ => (Err(anyhow!("expected at least 1 value")), Ok(0.)),
+                };
+                uvs.push((uv.0.with_context(at)?, uv.1.with_context(at)?));
+            }
+            "f" => {
+                let verts = args
+                    .iter()
+                    .map(|a| parse_face_vertex(a, positions.len(), uvs.len(), normals.len()))
+                    .collect::<Result<Vec<_>>>()
+                    .with_context(at)?;
+                if verts.len() < 3 {
+                    return Err(anyhow!("face has fewer than 3 vertices")).with_context(at);
+                }
+                for k in 1..verts.len() - 1 {
+                    groups[current].1.push([verts[0], verts[k], verts[k + 1]]);
+                }
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(anyhow!("mtllib without a filename")).with_context(at);
+                }
+                for name in args {
+                    materials.extend(load_mtl(dir.join(name)).with_context(at)?);
+                }
+            }
+            "usemtl" => {
+                let name = args
+                    .first()
+                    .ok_or_else(|| anyhow!("usemtl without a material name"))
+                    .with_context(at)?;
+                if !materials.contains_key(*name) {
+                    return Err(anyhow!("unknown material {}", name)).with_context(at);
+                }
+                current = match groups.iter().position(|(n, _)| n.as_deref() == Some(*name)) {
+                    Some(g) => g,
+                    None => {
+                        groups.push((Some(name.to_string()), vec![]));
+                        groups.len() - 1
+                    }
+                };
+            }
+            // grouping, smoothing, lines and points don't affect what we render
+            _ => {}
+        }
+    }
+
+    let default_material: Arc<dyn Material> =
+        Arc::new(material::Lambertian::from_color(Color::new(0.8, 0.8, 0.8)));
+
+    let mut world = HittableList::default();
+    for (name, tris) in groups.into_iter().filter(|(_, t)| !t.is_empty()) {
+        let material = match name {
+            Some(n) => Arc::clone(&materials[&n]),
+            None => Arc::clone(&default_material),
+        };
+        let mesh = build_mesh(&positions, &uvs, &normals, &tris, material);
+        for tri in mesh.into_hittable_list().into_inner() {
+            world.add(tri);
+        }
+    }
+    Ok(world)
+}
+
+// obj indexes positions, uvs and normals separately, but TriangleMesh shares one index across
+// all three, so every distinct combination becomes its own mesh vertex
+fn build_mesh(
+    positions: &[Point3],
+    uvs: &[(f64, f64)],
+    normals: &[Vec3],
+    tris: &[[FaceVertex; 3]],
+    material: Arc<dyn Material>,
+) -> TriangleMesh {
+    let has_uvs = tris.iter().flatten().all(|v| v.1.is_some());
+    let has_normals = tris.iter().flatten().all(|v| v.2.is_some());
+
+    let mut remap: HashMap<FaceVertex, usize> = HashMap::new();
+    let mut mesh_positions = vec![];
+    let mut mesh_uvs = vec![];
+    let mut mesh_normals = vec![];
+    let mut indices = vec![];
+
+    for tri in tris {
+        let mut idx = [0; 3];
+        for (k, v) in tri.iter().enumerate() {
+            idx[k] = *remap.entry(*v).or_insert_with(|| {
+                mesh_positions.push(positions[v.0]);
+                if has_uvs {
+                    mesh_uvs.push(uvs[v.1.unwrap()]);
+                }
+                if has_normals {
+                    mesh_normals.push(normals[v.2.unwrap()]);
+                }
+                mesh_positions.len() - 1
+            });
+        }
+        indices.push(idx);
+    }
+
+    TriangleMesh::new(
+        mesh_positions,
+        if has_normals {
+            Some(mesh_normals)
+        } else {
+            None
+        },
+        if has_uvs { Some(mesh_uvs) } else { None },
+        indices,
+        material,
+    )
+}
+
+// "v", "v/vt", "v//vn" or "v/vt/vn"
+fn parse_face_vertex(s: &str, n_pos: usize, n_uv: usize, n_norm: usize) -> Result<FaceVertex> {
+    let mut parts = s.split('/');
+    let v = resolve_index(parts.next().unwrap_or(""), n_pos, "vertex")?;
+    let vt = match parts.next() {
+        Some("") | None => None,
+        Some(t) => Some(resolve_index(t, n_uv, "texture coordinate")?),
+    };
+    let vn = match parts.next() {
+        Some("") | None => None,
+        Some(n) => Some(resolve_index(n, n_norm, "normal")?),
+    };
+    if parts.next().is_some() {
+        bail!("malformed face vertex {}", s);
+    }
+    Ok((v, vt, vn))
+}
+
+// obj indices are 1-based, and negative ones count back from the most recent element
+fn resolve_index(s: &str, count: usize, what: &str) -> Result<usize> {
+    let i: i64 = s
+        .parse()
+        .map_err(|_| anyhow!("invalid {} index {:?}", what, s))?;
+    let resolved = if i > 0 { i - 1 } else { count as i64 + i };
+    if i == 0 || resolved < 0 || resolved >= count as i64 {
+        bail!("{} index {} out of range ({} defined)", what, i, count);
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_num<T: FromStr>(s: &str) -> Result<T> {
+    s.parse().map_err(|_| anyhow!("invalid number {:?}", s))
+}
+
+fn parse_vec3(args: &[&str]) -> Result<Vec3> {
+    if args.len() < 3 {
+        bail!("expected 3 values, got {}", args.len());
+    }
+    Ok(Vec3::new(
+        parse_num(args[0])?,
+        parse_num(args[1])?,
+        parse_num(args[2])?,
+    ))
+}
+
+#[derive(Default)]
+struct MtlMaterial {
+    kd: Option<Color>,
+    ks: Option<Color>,
+    ke: Option<Color>,
+    ns: Option<f64>,
+    ni: Option<f64>,
+    dissolve: Option<f64>,
+    illum: Option<u32>,
+    map_kd: Option<String>,
+}
+
+fn load_mtl(path: impl AsRef<Path>) -> Result<HashMap<String, Arc<dyn Material>>> {
+    let path = path.as_ref();
+    let src =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    parse_mtl(&src, path)
+}
+
+// path is where src came from, for errors and to find textures next to it
+fn parse_mtl(src: &str, path: &Path) -> Result<HashMap<String, Arc<dyn Material>>> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut parsed: Vec<(String, MtlMaterial, usize)> = vec![];
+    for (i, line) in src.lines().enumerate() {
+        let at = || format!("{}:{}", path.display(), i + 1);
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            let name = args
+                .first()
+                .ok_or_else(|| anyhow!("newmtl without a name"))
+                .with_context(at)?;
+            parsed.push((name.to_string(), MtlMaterial::default(), i + 1));
+            continue;
+        }
+        let (_, mtl, _) = match parsed.last_mut() {
+            Some(m) => m,
+            // statements before the first newmtl have nothing to apply to
+            None => return Err(anyhow!("{} before newmtl", keyword)).with_context(at),
+        };
+        let single = || -> Result<f64> {
+            parse_num(
+                args.first()
+                    .ok_or_else(|| anyhow!("{} without a value", keyword))?,
+            )
+        };
+
+        match keyword {
+            "Kd" => mtl.kd = Some(parse_vec3(&args).with_context(at)?),
+            "Ks" => mtl.ks = Some(parse_vec3(&args).with_context(at)?),
+            "Ke" => mtl.ke = Some(parse_vec3(&args).with_context(at)?),
+            "Ns" => mtl.ns = Some(single().with_context(at)?),
+            "Ni" => mtl.ni = Some(single().with_context(at)?),
+            "d" => mtl.dissolve = Some(single().with_context(at)?),
+            "Tr" => mtl.dissolve = Some(1. - single().with_context(at)?),
+            "illum" => mtl.illum = Some(single().with_context(at)? as u32),
+            // the filename comes last, after any options like -s or -o
+            "map_Kd" => {
+                let file = args
+                    .last()
+                    .ok_or_else(|| anyhow!("map_Kd without a filename"))
+                    .with_context(at)?;
+                mtl.map_kd = Some(file.to_string());
+            }
+            _ => {}
+        }
+    }
+
+    parsed
+        .into_iter()
+        .map(|(name, mtl, line)| {
+            let material = to_material(&mtl, dir)
+                .with_context(|| format!("{}:{}: material {}", path.display(), line, name))?;
+            Ok((name, material))
+        })
+        .collect()
+}
+
+// mtl describes a phong-style material; pick whichever of ours it most resembles
+fn to_material(mtl: &MtlMaterial, dir: &Path) -> Result<Arc<dyn Material>> {
+    let kd = mtl.kd.unwrap_or_else(|| Color::new(0.8, 0.8, 0.8));
+    let ks = mtl.ks.unwrap_or_else(|| Color::new(0., 0., 0.));
+    let dissolve = mtl.dissolve.unwrap_or(1.);
+    let illum = mtl.illum.unwrap_or(2);
+
+    // anything glowing is treated as a light, whatever else it says
+    if let Some(ke) = mtl.ke.filter(|ke| ke.max() > 0.) {
+        return Ok(Arc::new(material::DiffuseLight::from_color(ke)));
+    }
+
+    // illumination models 4, 6, 7 and 9 are the glass-like ones
+    if dissolve < 1. || matches!(illum, 4 | 6 | 7 | 9) {
+        return Ok(Arc::new(material::Dielectric::new(mtl.ni.unwrap_or(1.5))));
+    }
+
+    // illum 3 turns on ray traced reflection; otherwise go metal only if specular dominates
+    if illum == 3 || ks.max() > kd.max() {
+        // map the phong exponent to a roughness-like fuzz
+        let fuzz = (2. / (mtl.ns.unwrap_or(0.) + 2.)).sqrt();
+        // with no specular color to reflect in, use the diffuse one rather than come out black
+        let albedo = Some(ks).filter(|ks| ks.max() > 0.).unwrap_or(kd);
+        return Ok(Arc::new(material::Metal::from_color(albedo, fuzz)));
+    }
+
+    match &mtl.map_kd {
+        Some(file) => {
+            let texture = ImageTexture::open(dir.join(file))?;
+            Ok(Arc::new(material::Lambertian::new(Arc::new(texture))))
+        }
+        None => Ok(Arc::new(material::Lambertian::from_color(kd))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::ray::Ray;
+    use crate::vec3::Unit;
+
+    fn parse(src: &str) -> Result<HittableList> {
+        parse_obj(src, Path::new("dir/mesh.obj"))
+    }
+
+    // the whole chain, the way main prints it
+    fn error(result: Result<impl Sized>) -> String {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => format!("{:#}", e),
+        }
+    }
+
+    fn down_at(x: f64, y: f64) -> Ray {
+        Ray::new(Point3::new(x, y, 1.), Vec3::new(0., 0., -1.), 0.)
+    }
+
+    // what the material does to light scattered off a surface facing +z
+    fn attenuation(material: &Arc<dyn Material>) -> Color {
+        let r = down_at(0., 0.);
+        let rec = HitRecord::from_ray_outward_normal_material(
+            Point3::zeros(),
+            1.,
+            &r,
+            Unit::new_normalize(Vec3::z()),
+            0.,
+            0.,
+            Arc::clone(material),
+        );
+        // fuzzy metals sometimes scatter into the surface
+        (0..100)
+            .find_map(|_| material.scatter(&r, &rec))
+            .expect("material never scatters")
+            .1
+    }
+
+    #[test]
+    fn quad_with_uvs_and_normals() {
+        let src = "\
+# a unit square in the xy plane
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+f 1/1/1 2/2/1 3/3/1 -1/-1/-1
+";
+        let world = parse(src).unwrap();
+        // fan-triangulated into two
+        assert_eq!(world.as_ref().len(), 2);
+        let rec = world
+            .hit(&down_at(0.25, 0.75), 0.001, f64::INFINITY)
+            .unwrap();
+        assert!((rec.u() - 0.25).abs() < 1e-12);
+        assert!((rec.v() - 0.75).abs() < 1e-12);
+        assert!((rec.normal().into_inner() - Vec3::z()).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn positions_only() {
+        let world = parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert_eq!(world.as_ref().len(), 1);
+        // no uvs in the file, so the barycentrics
+        let rec = world
+            .hit(&down_at(0.25, 0.5), 0.001, f64::INFINITY)
+            .unwrap();
+        assert!((rec.u() - 0.25).abs() < 1e-12);
+        assert!((rec.v() - 0.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn errors_say_where() {
+        let e = error(parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n"));
+        assert!(e.starts_with("dir/mesh.obj:3: "), "{}", e);
+        assert!(e.contains("vertex index 3 out of range"), "{}", e);
+
+        let e = error(parse("v 0 0 0\n\nv 1 zero 0\n"));
+        assert!(e.starts_with("dir/mesh.obj:3: "), "{}", e);
+        assert!(e.contains("invalid number \"zero\""), "{}", e);
+
+        let e = error(parse("v 0 0 0\nv 1 0 0\nf 1 2\n"));
+        assert!(e.starts_with("dir/mesh.obj:3: "), "{}", e);
+
+        let e = error(parse("v 0 0 0\nf 0 1 1\n"));
+        assert!(e.starts_with("dir/mesh.obj:2: "), "{}", e);
+
+        let e = error(parse("v 0 0 0\nf 1/1 1/1 1/1\n"));
+        assert!(
+            e.contains("texture coordinate index 1 out of range"),
+            "{}",
+            e
+        );
+
+        let e = error(parse("usemtl red\n"));
+        assert!(
+            e.starts_with("dir/mesh.obj:1: unknown material red"),
+            "{}",
+            e
+        );
+    }
+
+    #[test]
+    fn mtl_materials() {
+        let src = "\
+newmtl red
+Kd 0.8 0.1 0.1
+
+newmtl lamp
+Ke 4 4 4
+
+newmtl glass
+Ni 1.5
+d 0.5
+
+newmtl chrome # specular wins
+Kd 0.1 0.1 0.1
+Ks 0.9 0.9 0.9
+Ns 1000
+
+newmtl mirror
+Kd 0.5 0.6 0.7
+illum 3
+";
+        let materials = parse_mtl(src, Path::new("dir/lib.mtl")).unwrap();
+        assert_eq!(materials.len(), 5);
+        assert_eq!(attenuation(&materials["red"]), Color::new(0.8, 0.1, 0.1));
+        assert!(materials["lamp"].is_emissive());
+        assert!(!materials["red"].is_emissive());
+        assert_eq!(attenuation(&materials["glass"]), Color::new(1., 1., 1.));
+        assert_eq!(attenuation(&materials["chrome"]), Color::new(0.9, 0.9, 0.9));
+        assert_eq!(attenuation(&materials["mirror"]), Color::new(0.5, 0.6, 0.7));
+    }
+
+    #[test]
+    fn mtl_errors_say_where() {
+        let e = error(parse_mtl("Kd 1 1 1\n", Path::new("lib.mtl")));
+        assert!(e.starts_with("lib.mtl:1: Kd before newmtl"), "{}", e);
+
+        let e = error(parse_mtl("newmtl a\n\nKd 1 1\n", Path::new("lib.mtl")));
+        assert!(
+            e.starts_with("lib.mtl:3: expected 3 values, got 2"),
+            "{}",
+            e
+        );
+
+        // materials are only made once the whole file is read, but still say where they were
+        let e = error(parse_mtl(
+            "newmtl a\nmap_Kd missing.png\n",
+            Path::new("lib.mtl"),
+        ));
+        assert!(e.starts_with("lib.mtl:1: material a"), "{}", e);
+    }
+
+    #[test]
+    fn mtllib_next_to_the_obj() {
+        let dir = std::env::temp_dir().join(format!("rt-obj-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("lib.mtl"), "newmtl green\nKd 0.1 0.7 0.1\n").unwrap();
+        let src = "\
+mtllib lib.mtl
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+usemtl green
+f 3 2 1
+";
+        let world = parse_obj(src, &dir.join("mesh.obj"));
+        fs::remove_dir_all(&dir).unwrap();
+        let world = world.unwrap();
+
+        // the face before usemtl gets the default grey
+        let mut colors = world
+            .as_ref()
+            .iter()
+            .map(|tri| {
+                let rec = tri.hit(&down_at(0.25, 0.25), 0.001, f64::INFINITY).unwrap();
+                attenuation(&rec.material())
+            })
+            .collect::<Vec<_>>();
+        colors.sort_by(|a, b| a.y.partial_cmp(&b.y).unwrap());
+        assert_eq!(
+            colors,
+            vec![Color::new(0.1, 0.7, 0.1), Color::new(0.8, 0.8, 0.8)]
+        );
+
+        let e = error(parse_obj(
+            "v 0 0 0\nmtllib nope.mtl\n",
+            &dir.join("mesh.obj"),
+        ));
+        let at = format!("{}:2: failed to read", dir.join("mesh.obj").display());
+        assert!(e.starts_with(&at), "{}", e);
+    }
+}
@@ -0,0 +1,557 @@
+use crate::hittable::{HittableList, TriangleMesh};
+use crate::material::{self, Material};
+use crate::texture::{SolidColor, VertexColor};
+use crate::vec3::{Color, Point3, Vec3};
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+// stanford .ply meshes, ascii or binary in either byte order.
+// vertices may carry normals, uvs and colors; faces are fan-triangulated.
+// http://paulbourke.net/dataformats/ply/
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => bail!("unknown property type {:?}", s),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+
+    // what a color channel of this type reads as when fully on
+    fn color_scale(self) -> f64 {
+        match self {
+            ScalarType::U8 => 255.,
+            ScalarType::U16 => 65535.,
+            _ => 1.,
+        }
+    }
+}
+
+enum PropertyKind {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn property(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|p| names.contains(&p.name.as_str()))
+    }
+}
+
+// one element instance. scalars and lists are both indexed by property position, with the
+// slots of the other kind left unused
+#[derive(Default)]
+struct Record {
+    scalars: Vec<f64>,
+    lists: Vec<Vec<f64>>,
+}
+
+// reads element instances out of the body, which starts right after end_header
+struct BodyReader<'a> {
+    format: Format,
+    data: &'a [u8],
+    pos: usize,
+    // last line read, for ascii error messages
+    line: usize,
+}
+
+impl<'a> BodyReader<'a> {
+    fn read_record(&mut self, element: &Element, record: &mut Record) -> Result<()> {
+        record.scalars.clear();
+        record.scalars.resize(element.properties.len(), 0.);
+        record.lists.resize_with(element.properties.len(), Vec::new);
+
+        if self.format == Format::Ascii {
+            if self.pos >= self.data.len() {
+                bail!("unexpected end of file");
+            }
+            let end = self.data[self.pos..]
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(self.data.len(), |e| self.pos + e + 1);
+            self.line += 1;
+            let line = std::str::from_utf8(&self.data[self.pos..end])
+                .map_err(|_| anyhow!("not valid text"))?;
+            self.pos = end;
+            if line.trim().is_empty() {
+                // tolerate blank lines between records
+                return self.read_record(element, record);
+            }
+            let mut tokens = line.split_whitespace();
+            let mut next = || -> Result<f64> {
+                let tok = tokens
+                    .next()
+                    .ok_or_else(|| anyhow!("too few values for element {}", element.name))?;
+                tok.parse().map_err(|_| anyhow!("invalid number {:?}", tok))
+            };
+            for (i, p) in element.properties.iter().enumerate() {
+                match p.kind {
+                    PropertyKind::Scalar(_) => record.scalars[i] = next()?,
+                    PropertyKind::List { .. } => {
+                        let n = next()? as usize;
+                        record.lists[i].clear();
+                        for _ in 0..n {
+                            let v = next()?;
+                            record.lists[i].push(v);
+                        }
+                    }
+                }
+            }
+        } else {
+            for (i, p) in element.properties.iter().enumerate() {
+                match p.kind {
+                    PropertyKind::Scalar(ty) => record.scalars[i] = self.read_binary(ty)?,
+                    PropertyKind::List { count, item } => {
+                        let n = self.read_binary(count)? as usize;
+                        record.lists[i].clear();
+                        for _ in 0..n {
+                            let v = self.read_binary(item)?;
+                            record.lists[i].push(v);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn read_binary(&mut self, ty: ScalarType) -> Result<f64> {
+        let size = ty.size();
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + size)
+            .ok_or_else(|| anyhow!("unexpected end of file at byte {}", self.pos))?;
+        self.pos += size;
+
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        if self.format == Format::BinaryBigEndian {
+            buf[..size].reverse();
+        }
+        Ok(match ty {
+            ScalarType::I8 => buf[0] as i8 as f64,
+            ScalarType::U8 => buf[0] as f64,
+            ScalarType::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            ScalarType::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            ScalarType::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            ScalarType::F64 => f64::from_le_bytes(buf),
+        })
+    }
+
+    fn location(&self) -> String {
+        match self.format {
+            Format::Ascii => format!("line {}", self.line),
+            _ => format!("byte {}", self.pos),
+        }
+    }
+}
+
+pub fn load_ply<P: AsRef<Path>>(path: P) -> Result<HittableList> {
+    let path = path.as_ref();
+    let data = fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+    read_ply(&data).with_context(|| format!("failed to load {}", path.display()))
+}
+
+fn read_ply(data: &[u8]) -> Result<HittableList> {
+    let (elements, mut body) = read_header(data)?;
+
+    let mut positions: Vec<Point3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<(f64, f64)> = vec![];
+    let mut colors: Vec<Color> = vec![];
+    let mut indices: Vec<[usize; 3]> = vec![];
+    let mut record = Record::default();
+
+    for element in elements.iter() {
+        let at = |body: &BodyReader| format!("element {}, {}", element.name, body.location());
+        match element.name.as_str() {
+            "vertex" => {
+                let xyz = ["x", "y", "z"]
+                    .iter()
+                    .map(|n| element.property(&[n]))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| anyhow!("vertex element is missing x, y or z"))?;
+                let nxyz = ["nx", "ny", "nz"]
+                    .iter()
+                    .map(|n| element.property(&[n]))
+                    .collect::<Option<Vec<_>>>();
+                let uv = element
+                    .property(&["u", "s", "texture_u", "texture_s"])
+                    .zip(element.property(&["v", "t", "texture_v", "texture_t"]));
+                let rgb = ["red", "green", "blue"]
+                    .iter()
+                    .map(|n| element.property(&[n, &format!("diffuse_{}", n)]))
+                    .collect::<Option<Vec<_>>>();
+                let color_scale =
+                    rgb.as_ref()
+                        .map_or(1., |rgb| match element.properties[rgb[0]].kind {
+                            PropertyKind::Scalar(ty) => ty.color_scale(),
+                            PropertyKind::List { .. } => 1.,
+                        });
+
+                for _ in 0..element.count {
+                    body.read_record(element, &mut record)
+                        .with_context(|| at(&body))?;
+                    let s = &record.scalars;
+                    positions.push(Point3::new(s[xyz[0]], s[xyz[1]], s[xyz[2]]));
+                    if let Some(n) = &nxyz {
+                        normals.push(Vec3::new(s[n[0]], s[n[1]], s[n[2]]));
+                    }
+                    if let Some((u, v)) = uv {
+                        uvs.push((s[u], s[v]));
+                    }
+                    if let Some(c) = &rgb {
+                        colors.push(Color::new(s[c[0]], s[c[1]], s[c[2]]) / color_scale);
+                    }
+                }
+            }
+            "face" => {
+                let list = element
+                    .property(&["vertex_indices", "vertex_index"])
+                    .ok_or_else(|| anyhow!("face element has no vertex_indices list"))?;
+                for f in 0..element.count {
+                    body.read_record(element, &mut record)
+                        .with_context(|| at(&body))?;
+                    // ascii files can hold any number where an index goes
+                    let face = record.lists[list]
+                        .iter()
+                        .map(|&i| {
+                            if i >= 0. && i.fract() == 0. {
+                                Ok(i as usize)
+                            } else {
+                                Err(anyhow!("face {} has vertex index {}", f, i))
+                            }
+                        })
+                        .collect::<Result<Vec<_>>>()
+                        .with_context(|| at(&body))?;
+                    if face.len() < 3 {
+                        return Err(anyhow!("face has fewer than 3 vertices"))
+                            .with_context(|| at(&body));
+                    }
+                    for k in 1..face.len() - 1 {
+                        indices.push([face[0], face[k], face[k + 1]]);
+                    }
+                }
+            }
+            // anything else (edges, materials, ...) still has to be read past
+            _ => {
+                for _ in 0..element.count {
+                    body.read_record(element, &mut record)
+                        .with_context(|| at(&body))?;
+                }
+            }
+        }
+    }
+
+    if let Some(bad) = indices.iter().flatten().find(|&&i| i >= positions.len()) {
+        bail!(
+            "face refers to vertex {}, but there are only {}",
+            bad,
+            positions.len()
+        );
+    }
+
+    // the vertex colors tint a white albedo, so they show as they are
+    let material: Arc<dyn Material> = if colors.is_empty() {
+        Arc::new(material::Lambertian::from_color(Color::new(0.8, 0.8, 0.8)))
+    } else {
+        let white = Arc::new(SolidColor::new(Color::new(1., 1., 1.)));
+        Arc::new(material::Lambertian::new(Arc::new(VertexColor::new(white))))
+    };
+
+    let mesh = TriangleMesh::new(
+        positions,
+        Some(normals).filter(|n| !n.is_empty()),
+        Some(uvs).filter(|uv| !uv.is_empty()),
+        indices,
+        material,
+    );
+    let mesh = if colors.is_empty() {
+        mesh
+    } else {
+        mesh.with_colors(colors)
+    };
+    Ok(mesh.into_hittable_list())
+}
+
+fn read_header(data: &[u8]) -> Result<(Vec<Element>, BodyReader<'_>)> {
+    let mut pos = 0;
+    let mut lineno = 0;
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+
+    loop {
+        let end = data[pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| anyhow!("header has no end_header"))?;
+        let line = std::str::from_utf8(&data[pos..pos + end])
+            .map_err(|_| anyhow!("line {}: header is not valid text", lineno + 1))?
+            .trim_end_matches('\r');
+        pos += end + 1;
+        lineno += 1;
+        let at = || format!("line {}", lineno);
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if lineno == 1 {
+            if tokens != ["ply"] {
+                bail!("not a ply file");
+            }
+            continue;
+        }
+        match tokens.as_slice() {
+            ["format", f, _version] => {
+                format = Some(match *f {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(anyhow!("unknown format {:?}", f)).with_context(at),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count
+                    .parse()
+                    .map_err(|_| anyhow!("invalid element count {:?}", count))
+                    .with_context(at)?,
+                properties: vec![],
+            }),
+            ["property", "list", count, item, name] => {
+                let kind = PropertyKind::List {
+                    count: ScalarType::parse(count).with_context(at)?,
+                    item: ScalarType::parse(item).with_context(at)?,
+                };
+                push_property(&mut elements, name, kind).with_context(at)?;
+            }
+            ["property", ty, name] => {
+                let kind = PropertyKind::Scalar(ScalarType::parse(ty).with_context(at)?);
+                push_property(&mut elements, name, kind).with_context(at)?;
+            }
+            ["end_header"] => break,
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(anyhow!("unexpected header line {:?}", line)).with_context(at),
+        }
+    }
+
+    let format = format.ok_or_else(|| anyhow!("header has no format line"))?;
+    let body = BodyReader {
+        format,
+        data,
+        pos,
+        line: lineno,
+    };
+    Ok((elements, body))
+}
+
+fn push_property(elements: &mut [Element], name: &str, kind: PropertyKind) -> Result<()> {
+    let element = elements
+        .last_mut()
+        .ok_or_else(|| anyhow!("property before any element"))?;
+    element.properties.push(Property {
+        name: name.to_string(),
+        kind,
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::Hittable;
+    use crate::ray::Ray;
+
+    fn error(result: Result<impl Sized>) -> String {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => format!("{:#}", e),
+        }
+    }
+
+    fn down_at(x: f64, y: f64) -> Ray {
+        Ray::new(Point3::new(x, y, 1.), Vec3::new(0., 0., -1.), 0.)
+    }
+
+    const ASCII: &str = "\
+ply
+format ascii 1.0
+comment one triangle, with uvs and colors
+element vertex 3
+property float x
+property float y
+property float z
+property float s
+property float t
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 255 0 0
+1 0 0 1 0 0 255 0
+0 1 0 0 1 0 0 255
+3 0 1 2
+";
+
+    #[test]
+    fn ascii_with_uvs_and_colors() {
+        let world = read_ply(ASCII.as_bytes()).unwrap();
+        assert_eq!(world.as_ref().len(), 1);
+        let rec = world
+            .hit(&down_at(0.25, 0.5), 0.001, f64::INFINITY)
+            .unwrap();
+        // the file's own uvs survive the colors
+        assert!((rec.u() - 0.25).abs() < 1e-6);
+        assert!((rec.v() - 0.5).abs() < 1e-6);
+        let color = rec.color().unwrap();
+        assert!((color - Color::new(0.25, 0.25, 0.5)).magnitude() < 1e-6);
+        // and the material's texture sees them
+        let (_, attenuation) = rec.material().scatter(&down_at(0.25, 0.5), &rec).unwrap();
+        assert!((attenuation - color).magnitude() < 1e-6);
+    }
+
+    // a unit square as one quad, with float positions and int indices
+    fn binary(format: &str, to_bytes: fn(f32) -> [u8; 4], index: fn(i32) -> [u8; 4]) -> Vec<u8> {
+        let mut data = format!(
+            "ply\nformat {} 1.0\nelement vertex 4\nproperty float x\nproperty float y\n\
+             property float z\nelement face 1\nproperty list uchar int vertex_indices\n\
+             end_header\n",
+            format
+        )
+        .into_bytes();
+        for (x, y) in [(0., 0.), (1., 0.), (1., 1.), (0., 1.)] {
+            for c in [x, y, 0.] {
+                data.extend(to_bytes(c));
+            }
+        }
+        data.push(4);
+        for i in 0..4 {
+            data.extend(index(i));
+        }
+        data
+    }
+
+    #[test]
+    fn binary_both_byte_orders() {
+        let little = binary("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes);
+        let big = binary("binary_big_endian", f32::to_be_bytes, i32::to_be_bytes);
+        for data in [little, big] {
+            let world = read_ply(&data).unwrap();
+            // fan-triangulated into two
+            assert_eq!(world.as_ref().len(), 2);
+            for (x, y) in [(0.75, 0.25), (0.25, 0.75)] {
+                let rec = world.hit(&down_at(x, y), 0.001, f64::INFINITY).unwrap();
+                assert!((rec.t() - 1.).abs() < 1e-6);
+                assert!(rec.color().is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn errors_say_where() {
+        let bad_number = ASCII.replace("1 0 0 1 0 0 255 0", "1 0 0 1 0 0 lots 0");
+        let e = error(read_ply(bad_number.as_bytes()));
+        assert!(e.starts_with("element vertex, line 17: "), "{}", e);
+        assert!(e.contains("invalid number \"lots\""), "{}", e);
+
+        let short = ASCII.replace("3 0 1 2\n", "3 0 1\n");
+        let e = error(read_ply(short.as_bytes()));
+        assert!(e.starts_with("element face, line 19: "), "{}", e);
+
+        let out_of_range = ASCII.replace("3 0 1 2\n", "3 0 1 5\n");
+        let e = error(read_ply(out_of_range.as_bytes()));
+        assert!(
+            e.contains("face refers to vertex 5, but there are only 3"),
+            "{}",
+            e
+        );
+
+        for index in ["-1", "1.5"] {
+            let bad_index = ASCII.replace("3 0 1 2\n", &format!("3 0 {} 2\n", index));
+            let e = error(read_ply(bad_index.as_bytes()));
+            let expected = format!("element face, line 19: face 0 has vertex index {}", index);
+            assert!(e.starts_with(&expected), "{}", e);
+        }
+
+        let e = error(read_ply(ASCII.replace("ascii", "utf8").as_bytes()));
+        assert!(e.starts_with("line 2: unknown format \"utf8\""), "{}", e);
+
+        let e = error(read_ply(
+            b"ply\nformat ascii 1.0\nproperty float x\nend_header\n",
+        ));
+        assert!(
+            e.starts_with("line 3: property before any element"),
+            "{}",
+            e
+        );
+
+        // the last index cut short
+        let mut truncated = binary("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes);
+        let body = truncated.len() - (4 * 12 + 1 + 4 * 4);
+        truncated.truncate(truncated.len() - 2);
+        let e = error(read_ply(&truncated));
+        let at = body + 4 * 12 + 1 + 3 * 4;
+        assert!(
+            e.starts_with(&format!("element face, byte {}: ", at)),
+            "{}",
+            e
+        );
+        assert!(e.contains("unexpected end of file"), "{}", e);
+
+        let e = error(read_ply(b"obj\n"));
+        assert!(e.contains("not a ply file"), "{}", e);
+    }
+}
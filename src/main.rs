@@ -1,72 +1,75 @@
 // (lsp-rust-analyzer-inlay-hints-mode -1)
 
+mod background;
 mod camera;
 mod hittable;
+mod integrator;
+mod loader;
 mod material;
 mod output;
 mod random_scene;
 mod ray;
+mod sampler;
+mod sampling;
+mod scene;
 mod texture;
 mod vec3;
 
 use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
 
-use camera::Camera;
-use ray::Ray;
-use vec3::{Color, Point3, Vec3};
+use integrator::Integrator;
+use sampling::Heuristic;
+use scene::Scene;
+use vec3::Color;
 
+use anyhow::{bail, Result};
 use indicatif::{ProgressBar, ProgressStyle};
-use rand::prelude::*;
 use rayon::prelude::*;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 #[structopt[name = "rt"]]
 struct Opt {
-    #[structopt(short, long, default_value = "1200")] // 1200
-    width: u32,
-    #[structopt(short, long, default_value = "500")] // 500
-    samples_per_pixel: u32,
+    /// overrides the scene's image width
+    #[structopt(short, long)]
+    width: Option<u32>,
+    /// overrides the scene's samples per pixel
+    #[structopt(short, long)]
+    samples_per_pixel: Option<u32>,
     #[structopt(short, long)]
     no_use_rayon: bool,
+    /// built-in scene (rand|bouncing|2spheres|pyramid|cornell|cornell_smoke), a .toml scene file, or a model file
     #[structopt(long, default_value = "rand")]
     scene: String,
+    /// acceleration structure to build over the scene: none|bvh|sah|linear
+    #[structopt(long, default_value = "bvh")]
+    accel: String,
+    /// how to weigh light samples against scattered rays: power|balance
+    #[structopt(long, default_value = "power")]
+    mis: String,
+    /// how light is worked out: path|direct|bdpt|photons|mlt|ao, or a debug view: normals|uv|depth|heatmap (with --features heatmap)
+    #[structopt(long, default_value = "path")]
+    integrator: String,
+    /// bounces before paths may be ended early at random
+    #[structopt(long, default_value = "3")]
+    min_depth: u32,
+    /// bounces after which paths are always ended
+    #[structopt(long, default_value = "50")]
+    max_depth: u32,
+    /// photons sent out from the lights each pass, for the photons integrator
+    #[structopt(long, default_value = "100000")]
+    photons: usize,
+    /// radius photons are gathered from at first, for the photons integrator; it shrinks every
+    /// pass. defaults to a hundredth of the distance to whatever is in the middle of the view
+    #[structopt(long)]
+    photon_radius: Option<f64>,
 }
 
 // https://plasma-umass.org/coz/
 // https://github.com/plasma-umass/coz/tree/master/rust
 
-// for testing; pretty gradient
-// linearly blends white and blue depending on height of y coord after scaling the ray to unit
-fn ray_color(r: &Ray, world: &impl hittable::Hittable, depth: i64) -> Color {
-    use nalgebra::Unit;
-
-    if depth <= 0 {
-        return Color::new(0.0, 0.0, 0.0);
-    }
-
-    if let Some(rec) = world.hit(r, 0.001, std::f64::INFINITY) {
-        // recurse for diffusion / ray bouncing
-        if let Some((scattered, attenuation)) = rec.material().scatter(&r, &rec) {
-            let new_color = ray_color(&scattered, world, depth - 1);
-            // should be attenuation * ray_color(&scattered, world, depth - 1); where * is elementwise. is there a way to do this in nalg
-            // https://docs.rs/nalgebra/0.25.3/nalgebra/base/struct.Matrix.html#method.mul
-            // element-wise multiplication? transpose?
-            let multpld = Vec3::new(
-                new_color.x * attenuation.x,
-                new_color.y * attenuation.y,
-                new_color.z * attenuation.z,
-            );
-            return multpld;
-        }
-        return Color::new(0., 0., 0.);
-    }
-    let unit_direction = Unit::new_normalize(r.direction());
-    let t = 0.5 * (unit_direction.y + 1.0);
-    (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0)
-}
-
-fn main() {
+fn main() -> Result<()> {
     // coz doesnt work now that i have rayon...
     coz::thread_init();
 
@@ -75,77 +78,200 @@ fn main() {
     // eye is at 0,0,0; y is up, x is right, z is into the screen
     // traverse the screen from upper left, use 2 offset vercors along the sides to move the ray endpoint across the screen
 
+    let scene = match &opt.scene[..] {
+        "rand" => random_scene::random_scene(),
+        "bouncing" => random_scene::bouncing_spheres(),
+        "2spheres" => random_scene::two_spheres(),
+        "pyramid" => random_scene::pyramid(),
+        "cornell" => random_scene::cornell_box(),
+        "cornell_smoke" => random_scene::cornell_smoke(),
+        path if path.ends_with(".toml") => Scene::load(path)?,
+        path if [".obj", ".ply", ".gltf", ".glb"]
+            .iter()
+            .any(|ext| path.ends_with(ext)) =>
+        {
+            Scene::from_model(path)
+        }
+        _ => bail!("unknown scene: {}", opt.scene),
+    };
+
     // image
-    let aspect_ratio = 16.0 / 9.0;
-    let image_width = opt.width; // 3840
+    let aspect_ratio = scene.image.aspect_ratio;
+    let image_width = opt.width.unwrap_or(scene.image.width); // 3840
     let image_height = (image_width as f64 / aspect_ratio) as u32;
-    let samples_per_pixel = opt.samples_per_pixel;
-    let max_depth = 50; // max ray bounces
+    // pixels are spread from one edge of the view to the other, which takes two of them
+    if image_width < 2 || image_height < 2 {
+        bail!(
+            "the image has to be at least 2x2, not {}x{}",
+            image_width,
+            image_height
+        );
+    }
+    let samples_per_pixel = opt
+        .samples_per_pixel
+        .unwrap_or(scene.image.samples_per_pixel);
 
-    // world
-    let world = match &opt.scene[..] {
-        "rand" => random_scene::random_scene(),
-        "2spheres" => random_scene::two_spheres(),
-        _ => panic!("unknown scene: {}", opt.scene),
+    let heuristic = match &opt.mis[..] {
+        "power" => Heuristic::Power,
+        "balance" => Heuristic::Balance,
+        _ => bail!("unknown mis heuristic: {}", opt.mis),
     };
 
-    // camera
-    // depth of fieldx
-    let lookfrom = Point3::new(13., 2., 3.);
-    let lookat = Point3::new(0., 0., 0.);
-    let vup = Vec3::new(0., 1., 0.);
-    let dist_to_focus = 10.;
-    let aperture = 0.1;
-    let camera = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        20.,
-        aspect_ratio,
-        aperture,
-        dist_to_focus,
-    );
+    // world and camera
+    let (world, lights, camera) = scene.build()?;
+    let background = scene.background()?;
+    let objects: Box<dyn hittable::Hittable> = match &opt.accel[..] {
+        "none" => Box::new(world),
+        "bvh" => Box::new(hittable::BvhNode::from_hittable_list(world)?),
+        "sah" => {
+            let bvh = hittable::SahBvh::from_hittable_list(world)?;
+            eprintln!("sah bvh: {}", bvh.stats());
+            Box::new(bvh)
+        }
+        "linear" => {
+            let bvh = hittable::LinearBvh::from_hittable_list(world)?;
+            eprintln!(
+                "linear bvh: {} nodes, {} primitives",
+                bvh.node_count(),
+                bvh.primitive_count()
+            );
+            Box::new(bvh)
+        }
+        _ => bail!("unknown accel: {}", opt.accel),
+    };
+    let world = integrator::World {
+        objects,
+        lights,
+        background,
+    };
+
+    // how far away whatever is in the middle of the view is, for sizing things by what the
+    // camera sees rather than by the whole scene, which a huge ground sphere can make enormous
+    let center = camera.get_ray(0.5, 0.5);
+    let view_distance = world
+        .hit(&center)
+        .map(|rec| rec.t() * center.direction().magnitude());
+
+    // light reaching the image other than through the pixels' own samples
+    let splats = Arc::new(output::Splats::new(image_width, image_height));
+
+    let mut integrator: Box<dyn Integrator> = match &opt.integrator[..] {
+        "path" => Box::new(integrator::Path::new(
+            opt.min_depth,
+            opt.max_depth,
+            heuristic,
+        )),
+        "direct" => Box::new(integrator::Direct::new(opt.max_depth, heuristic)),
+        "bdpt" => Box::new(integrator::Bdpt::new(
+            camera.clone(),
+            image_width,
+            image_height,
+            Arc::clone(&splats),
+            opt.min_depth,
+            opt.max_depth,
+            heuristic,
+        )),
+        "mlt" => Box::new(integrator::Mlt::new(
+            camera.clone(),
+            image_width,
+            image_height,
+            Arc::clone(&splats),
+            integrator::Path::new(opt.min_depth, opt.max_depth, heuristic),
+        )),
+        "photons" => {
+            let radius = opt
+                .photon_radius
+                .unwrap_or_else(|| view_distance.map_or(1., |d| 0.01 * d));
+            Box::new(integrator::PhotonMapping::new(
+                opt.photons,
+                radius,
+                opt.min_depth,
+                opt.max_depth,
+                heuristic,
+            ))
+        }
+        "ao" => {
+            // a tenth of the way to the middle of the view
+            let radius = view_distance.map_or(1., |d| 0.1 * d);
+            Box::new(integrator::AmbientOcclusion::new(radius))
+        }
+        "normals" => Box::new(integrator::Normals),
+        "uv" => Box::new(integrator::Uvs),
+        "depth" => {
+            // whatever is in the middle of the view comes out mid grey
+            let scale = view_distance.map_or(1., |d| d / 2f64.ln());
+            Box::new(integrator::Depth::new(scale))
+        }
+        #[cfg(feature = "heatmap")]
+        "heatmap" => Box::new(integrator::Heatmap::new(100)),
+        #[cfg(not(feature = "heatmap"))]
+        "heatmap" => bail!("the heatmap needs building with --features heatmap"),
+        _ => bail!("unknown integrator: {}", opt.integrator),
+    };
+
+    // progressive integrators take one sample per pixel per pass
+    let (passes, samples_per_pass) = if integrator.progressive() {
+        (samples_per_pixel, 1)
+    } else {
+        (1, samples_per_pixel)
+    };
 
     // render
     let (mut tx, rx) = channel::<(u32, u32, Color)>(); // is this usage of channel too expensive?
+    let reader_splats = Arc::clone(&splats);
     let reader_thread = std::thread::spawn(move || {
         let mut buf =
             output::ImageOutput::new(image_width, image_height, samples_per_pixel, "out.png");
         while let Ok((x, y, color)) = rx.recv() {
             buf.put_pixel_color(x, y, color);
         }
+        // the senders are all gone, so nothing is rendering any more
+        buf.add_splats(&reader_splats);
         buf.save().expect("failed to save image");
     });
 
-    let bar = ProgressBar::new(image_height.into()).with_style(
+    // splats only integrators do all their work in start_pass, so those count a pass at a time
+    let splats_only = integrator.splats_only();
+    let steps = if splats_only { 1 } else { image_height };
+    let bar = ProgressBar::new(u64::from(steps) * u64::from(passes)).with_style(
         ProgressStyle::default_bar()
             .template("[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}")
             .progress_chars("##-"),
     );
 
-    let render_line = |tx: &mut Sender<(u32, u32, Color)>, &j| {
-        coz::scope!("scanline");
-        let mut rng = rand::thread_rng();
-        for i in 0..image_width {
-            let mut color = Color::new(0., 0., 0.);
-            for _ in 0..samples_per_pixel {
-                let u = (i as f64 + rng.gen::<f64>()) / (image_width as f64 - 1.0);
-                let v = (j as f64 + rng.gen::<f64>()) / (image_height as f64 - 1.0);
-                let r = camera.get_ray(u, v);
-                color += ray_color(&r, &world, max_depth);
-            }
-            // image was upside down for some reason..
-            tx.send((i, image_height - j - 1, color)).unwrap();
-        }
-        bar.inc(1);
-    };
-
     let lines = (0..=(image_height - 1)).collect::<Vec<_>>();
     if opt.no_use_rayon {
         eprintln!("running sans rayon");
-        lines.iter().for_each(|j| render_line(&mut tx, j));
-    } else {
-        lines.par_iter().for_each_with(tx.clone(), render_line);
+    }
+    for pass in 0..passes {
+        integrator.start_pass(&world, pass);
+        if splats_only {
+            bar.inc(1);
+            continue;
+        }
+        let integrator = &*integrator;
+
+        let render_line = |tx: &mut Sender<(u32, u32, Color)>, &j| {
+            coz::scope!("scanline");
+            for i in 0..image_width {
+                let mut color = Color::new(0., 0., 0.);
+                for _ in 0..samples_per_pass {
+                    let u = (i as f64 + sampler::random()) / (image_width as f64 - 1.0);
+                    let v = (j as f64 + sampler::random()) / (image_height as f64 - 1.0);
+                    let r = camera.get_ray(u, v);
+                    color += integrator.ray_color(&r, &world);
+                }
+                // image was upside down for some reason..
+                tx.send((i, image_height - j - 1, color)).unwrap();
+            }
+            bar.inc(1);
+        };
+
+        if opt.no_use_rayon {
+            lines.iter().for_each(|j| render_line(&mut tx, j));
+        } else {
+            lines.par_iter().for_each_with(tx.clone(), render_line);
+        }
     }
 
     bar.finish();
@@ -153,4 +279,5 @@ fn main() {
 
     reader_thread.join().expect("failed to join reader thread");
     eprintln!("\ndone");
+    Ok(())
 }
@@ -1,8 +1,8 @@
 use crate::hittable::HitRecord;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::sampler;
 use crate::vec3::{Color, Unit};
-use rand::Rng;
 
 pub struct Dielectric {
     ir: f64, // index of refraction
@@ -36,14 +36,14 @@ impl Material for Dielectric {
         let sin_theta = (1. - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.;
-        let rand = rand::thread_rng().gen();
+        let rand = sampler::random();
         let direction = if cannot_refract || Self::reflectance(cos_theta, refraction_ratio) > rand {
             crate::vec3::reflect(&unit_direction, &rec.normal())
         } else {
             crate::vec3::refract(&unit_direction, &rec.normal(), refraction_ratio)
         };
 
-        let scattered = Ray::new(rec.p(), direction);
+        let scattered = Ray::new(rec.p(), direction, r_in.time());
         Some((scattered, attenuation))
     }
 }
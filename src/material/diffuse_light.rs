@@ -0,0 +1,38 @@
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
+use crate::vec3::Color;
+
+use std::sync::Arc;
+
+// emits light and absorbs everything that hits it
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Arc<dyn Texture>) -> Self {
+        Self { emit }
+    }
+
+    pub fn from_color(emit: Color) -> Self {
+        Self {
+            emit: Arc::new(SolidColor::new(emit)),
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord) -> Option<(Ray, Color)> {
+        None
+    }
+
+    fn is_emissive(&self) -> bool {
+        true
+    }
+
+    fn emitted(&self, rec: &HitRecord) -> Color {
+        self.emit.value_at(rec)
+    }
+}
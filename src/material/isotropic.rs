@@ -0,0 +1,43 @@
+use crate::hittable::HitRecord;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vec3::Color;
+
+use std::sync::Arc;
+
+// scatters the same in every direction; the phase function of a constant medium
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Arc<dyn Texture>) -> Self {
+        Self { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+        let scattered = Ray::new(
+            rec.p(),
+            crate::vec3::random_unit().into_inner(),
+            r_in.time(),
+        );
+        let attenuation = self.albedo.value_at(rec);
+        Some((scattered, attenuation))
+    }
+
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Option<f64> {
+        Some(1. / (4. * std::f64::consts::PI))
+    }
+
+    fn bsdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let pdf = self.scattering_pdf(r_in, rec, scattered)?;
+        Some(self.albedo.value_at(rec) * pdf)
+    }
+
+    fn is_surface(&self) -> bool {
+        false
+    }
+}
@@ -23,16 +23,30 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)> {
         let mut scatter_direction =
-            rec.normal().clone().into_inner() + crate::vec3::random_unit().into_inner();
+            rec.normal().into_inner() + crate::vec3::random_unit().into_inner();
 
         // catch degenerate scatter direction
         if crate::vec3::near_zero(&scatter_direction) {
-            scatter_direction = rec.normal().clone().into_inner();
+            scatter_direction = rec.normal().into_inner();
         }
 
-        let attenuation = self.albedo.value(rec.u(), rec.v(), rec.p());
-        Some((Ray::new(rec.p(), scatter_direction), attenuation))
+        let attenuation = self.albedo.value_at(rec);
+        Some((
+            Ray::new(rec.p(), scatter_direction, r_in.time()),
+            attenuation,
+        ))
+    }
+
+    // cosine weighted, which is what normal + random_unit comes out as
+    fn scattering_pdf(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<f64> {
+        let cosine = rec.normal().dot(&scattered.direction().normalize());
+        Some(cosine.max(0.) / std::f64::consts::PI)
+    }
+
+    fn bsdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        let pdf = self.scattering_pdf(r_in, rec, scattered)?;
+        Some(self.albedo.value_at(rec) * pdf)
     }
 }
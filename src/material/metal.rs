@@ -1,17 +1,25 @@
 use crate::hittable::HitRecord;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::texture::{SolidColor, Texture};
 use crate::vec3::{Color, Unit};
 
+use std::f64::consts::PI;
+use std::sync::Arc;
+
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
 }
 
 impl Metal {
-    pub fn new(albedo: Color, fuzz: f64) -> Self {
+    pub fn new(albedo: Arc<dyn Texture>, fuzz: f64) -> Self {
         Self { albedo, fuzz }
     }
+
+    pub fn from_color(albedo: Color, fuzz: f64) -> Self {
+        Self::new(Arc::new(SolidColor::new(albedo)), fuzz)
+    }
 }
 
 impl Material for Metal {
@@ -20,11 +28,39 @@ impl Material for Metal {
         let scattered = Ray::new(
             rec.p(),
             reflected + self.fuzz * crate::vec3::random_in_unit_sphere(),
+            r_in.time(),
         );
         if scattered.direction().dot(&rec.normal()) > 0. {
-            Some((scattered, self.albedo))
+            Some((scattered, self.albedo.value_at(rec)))
         } else {
             None
         }
     }
+
+    // scatter picks a point uniformly in a ball of radius fuzz around the mirror direction;
+    // the density of a direction is the ball's volume along it, over the whole volume
+    fn scattering_pdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<f64> {
+        if self.fuzz <= 0. {
+            return None;
+        }
+        let direction = scattered.direction().normalize();
+        if direction.dot(&rec.normal()) <= 0. {
+            return Some(0.);
+        }
+        let reflected = crate::vec3::reflect(&Unit::new_normalize(r_in.direction()), &rec.normal());
+        // where the line along direction enters and leaves the ball
+        let b = direction.dot(&reflected);
+        let c = reflected.magnitude_squared() - self.fuzz * self.fuzz;
+        let discriminant = b * b - c;
+        if discriminant <= 0. || (c > 0. && b <= 0.) {
+            return Some(0.);
+        }
+        let near = if c > 0. { b - discriminant.sqrt() } else { 0. };
+        let far = b + discriminant.sqrt();
+        Some((far.powi(3) - near.powi(3)) / (4. * PI * self.fuzz.powi(3)))
+    }
+
+    fn bsdf(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Option<Color> {
+        Some(self.albedo.value_at(rec) * self.scattering_pdf(r_in, rec, scattered)?)
+    }
 }
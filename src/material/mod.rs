@@ -1,16 +1,49 @@
 use crate::hittable::HitRecord;
 use crate::ray::Ray;
-use crate::vec3::{Color};
+use crate::vec3::Color;
 
 mod dielectric;
+mod diffuse_light;
+mod isotropic;
 mod lambertian;
 mod metal;
 
 pub use dielectric::*;
+pub use diffuse_light::*;
+pub use isotropic::*;
 pub use lambertian::*;
 pub use metal::*;
 
 pub trait Material: Send + Sync {
     // -> scattered ray, attenuation
     fn scatter(&self, r_in: &Ray, rec: &HitRecord) -> Option<(Ray, Color)>;
+
+    // density with respect to solid angle of scatter sending r_in off along scattered; the
+    // attenuation times this is the brdf times the cosine term. None for materials that only
+    // scatter into one direction, which can't be lit by sampling lights. the integrator weighs
+    // light samples against scattered rays with it
+    fn scattering_pdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Option<f64> {
+        None
+    }
+
+    // the brdf times the cosine term for any pair of directions: the attenuation scatter would
+    // give times scattering_pdf. None likewise
+    fn bsdf(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Option<Color> {
+        None
+    }
+
+    // whether it scatters at surfaces, where the cosine terms apply, rather than in volumes
+    fn is_surface(&self) -> bool {
+        true
+    }
+
+    // whether emitted can give off any light at all
+    fn is_emissive(&self) -> bool {
+        false
+    }
+
+    // light given off where a ray hit; most materials don't
+    fn emitted(&self, _rec: &HitRecord) -> Color {
+        Color::new(0., 0., 0.)
+    }
 }
@@ -2,9 +2,12 @@ use crate::vec3::Color;
 use anyhow::Result;
 use image::{Rgb, RgbImage};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 pub struct ImageOutput {
-    img: RgbImage,
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>,
     filename: PathBuf,
     samples_per_pixel: u32,
 }
@@ -17,18 +20,37 @@ impl ImageOutput {
         filename: P,
     ) -> Self {
         Self {
-            img: RgbImage::new(width, height),
+            width,
+            height,
+            pixels: vec![Color::zeros(); (width * height) as usize],
             filename: filename.as_ref().to_owned(),
             samples_per_pixel,
         }
     }
 
     pub fn save(&mut self) -> Result<()> {
-        self.img.save(&self.filename)?;
+        let mut img = RgbImage::new(self.width, self.height);
+        for (i, c) in self.pixels.iter().enumerate() {
+            let i = i as u32;
+            img.put_pixel(i % self.width, i / self.width, self.to_rgb(c));
+        }
+        img.save(&self.filename)?;
         Ok(())
     }
 
+    // c is the sum of all the pixel's samples
     pub fn put_pixel_color(&mut self, x: u32, y: u32, c: Color) {
+        self.pixels[(y * self.width + x) as usize] += c;
+    }
+
+    // light that reached the camera by other paths than the pixels' own samples
+    pub fn add_splats(&mut self, splats: &Splats) {
+        for (pixel, splat) in self.pixels.iter_mut().zip(&splats.pixels) {
+            *pixel += Color::from_fn(|i, _| f64::from_bits(splat[i].load(Ordering::Relaxed)));
+        }
+    }
+
+    fn to_rgb(&self, c: &Color) -> Rgb<u8> {
         let mut r = c.x;
         let mut g = c.y;
         let mut b = c.z;
@@ -43,7 +65,44 @@ impl ImageOutput {
         let g = (256. * g.clamp(0., 0.999)) as u8;
         let b = (256. * b.clamp(0., 0.999)) as u8;
 
-        let rgb = Rgb([r, g, b]);
-        self.img.put_pixel(x, y, rgb);
+        Rgb([r, g, b])
+    }
+}
+
+// light added to any pixel from any thread while rendering, weighed like a sample's color.
+// the channels are f64 bits, added to by compare and swap
+pub struct Splats {
+    width: u32,
+    pixels: Vec<[AtomicU64; 3]>,
+}
+
+impl Splats {
+    pub fn new(width: u32, height: u32) -> Self {
+        let zero = || AtomicU64::new(0f64.to_bits());
+        Self {
+            width,
+            pixels: (0..width * height)
+                .map(|_| [zero(), zero(), zero()])
+                .collect(),
+        }
+    }
+
+    pub fn add(&self, x: u32, y: u32, c: Color) {
+        let pixel = &self.pixels[(y * self.width + x) as usize];
+        for (channel, value) in pixel.iter().zip(c.iter()) {
+            let mut current = channel.load(Ordering::Relaxed);
+            loop {
+                let new = (f64::from_bits(current) + value).to_bits();
+                match channel.compare_exchange_weak(
+                    current,
+                    new,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => break,
+                    Err(actual) => current = actual,
+                }
+            }
+        }
     }
 }
@@ -1,95 +1,300 @@
-use crate::hittable::{Hittable, HittableList, Sphere};
-use crate::material;
-use crate::texture;
-use crate::vec3::{Color, Point3};
+use crate::scene::{
+    default_checker_size, BackgroundDesc, CameraSettings, ImageSettings, MaterialDesc, MaterialRef,
+    ObjectDesc, Scene, ShapeRef, TextureDesc, TextureRef, TransformOp,
+};
+use crate::vec3::Vec3;
 use rand::prelude::*;
-use std::sync::Arc;
 
-pub fn two_spheres() -> HittableList {
-    let mut world = HittableList::new(vec![]);
+// the built-in scenes, as scene descriptions. scenes/*.toml spell out the fixed ones as files
 
-    let checker = Arc::new(material::Lambertian::new(Arc::new(
-        texture::Checker::from_colors(Color::new(0.2, 0.3, 0.1), Color::new(0.9, 0.9, 0.9)),
-    )));
+// the checkered lambertian, named "ground"
+fn checker_material(scene: &mut Scene) {
+    scene.textures.insert(
+        "checker".to_string(),
+        TextureDesc::Checker {
+            odd: TextureRef::Color([0.2, 0.3, 0.1]),
+            even: TextureRef::Color([0.9, 0.9, 0.9]),
+            size: default_checker_size(),
+        },
+    );
+    scene.materials.insert(
+        "ground".to_string(),
+        MaterialDesc::Lambertian {
+            albedo: TextureRef::Named("checker".to_string()),
+        },
+    );
+}
+
+fn checker_ground(scene: &mut Scene) {
+    checker_material(scene);
+    scene.objects.push(ObjectDesc::Sphere {
+        center: [0., -1000., 0.],
+        radius: 1000.,
+        material: MaterialRef::Named("ground".to_string()),
+    });
+}
 
-    world.add(Arc::new(Sphere::new(
-        Point3::new(0., -10., 0.),
-        10.,
-        checker.clone(),
-    )));
+pub fn two_spheres() -> Scene {
+    let mut scene = Scene::default();
 
-    world.add(Arc::new(Sphere::new(
-        Point3::new(0., 10., 0.),
-        10.,
-        checker.clone(),
-    )));
+    checker_material(&mut scene);
+    for y in [-10., 10.] {
+        scene.objects.push(ObjectDesc::Sphere {
+            center: [0., y, 0.],
+            radius: 10.,
+            material: MaterialRef::Named("ground".to_string()),
+        });
+    }
 
-    world
+    scene
 }
 
-pub fn random_scene() -> HittableList {
-    let mut world = HittableList::new(vec![]);
+// a textured square pyramid mesh and a standalone metal triangle
+pub fn pyramid() -> Scene {
+    let mut scene = Scene::default();
+
+    checker_ground(&mut scene);
+
+    // four sides, each with its own apex vertex so the uvs can differ per face
+    let corners = [
+        [-1.5, 0., 1.5],
+        [1.5, 0., 1.5],
+        [1.5, 0., -1.5],
+        [-1.5, 0., -1.5],
+    ];
+    let apex = [0., 2.5, 0.];
+    let mut positions = vec![];
+    let mut uvs = vec![];
+    let mut indices = vec![];
+    for i in 0..4 {
+        let base = positions.len();
+        positions.extend(&[corners[i], corners[(i + 1) % 4], apex]);
+        uvs.extend(&[[0., 0.], [1., 0.], [0.5, 1.]]);
+        indices.push([base, base + 1, base + 2]);
+    }
+    scene.objects.push(ObjectDesc::Mesh {
+        positions,
+        normals: None,
+        uvs: Some(uvs),
+        indices,
+        material: MaterialRef::Inline(MaterialDesc::Lambertian {
+            albedo: TextureRef::Inline(Box::new(TextureDesc::UvChecker {
+                odd: TextureRef::Color([0.7, 0.3, 0.2]),
+                even: TextureRef::Color([0.9, 0.8, 0.6]),
+                tiles: [4, 4],
+            })),
+        }),
+    });
 
-    let checker = Arc::new(texture::Checker::from_colors(
-        Color::new(0.2, 0.3, 0.1),
-        Color::new(0.9, 0.9, 0.9),
-    ));
+    scene.objects.push(ObjectDesc::Triangle {
+        vertices: [[3., 0., -3.], [5., 0., -1.], [4., 2.5, -2.]],
+        material: MaterialRef::Inline(MaterialDesc::Metal {
+            albedo: TextureRef::Color([0.8, 0.8, 0.8]),
+            fuzz: 0.05,
+        }),
+    });
+
+    scene
+}
+
+pub fn random_scene() -> Scene {
+    random_spheres(false)
+}
+
+// the same, but the diffuse spheres bounce up during the exposure
+pub fn bouncing_spheres() -> Scene {
+    let mut scene = random_spheres(true);
+    scene.camera = Some(CameraSettings {
+        shutter: [0., 1.],
+        ..Default::default()
+    });
+    scene
+}
 
-    // let material_ground = Arc::new(material::Lambertian::new(Color::new(0.5, 0.5, 0.5)));
-    world.add(Arc::new(Sphere::new(
-        Point3::new(0., -1000., 0.),
-        1000.,
-        Arc::new(material::Lambertian::new(checker)),
-    )));
+fn random_spheres(bouncing: bool) -> Scene {
+    let mut scene = Scene::default();
+
+    checker_ground(&mut scene);
 
     let mut rng = rand::thread_rng();
+    let to_array = |v: Vec3| [v.x, v.y, v.z];
     for a in -11..11 {
         for b in -11..11 {
             let choose_mat = rng.gen::<f64>();
-            let center = Point3::new(
+            let center = [
                 a as f64 + 0.9 * rng.gen::<f64>(),
                 0.2,
                 b as f64 + 0.9 * rng.gen::<f64>(),
-            );
-
-            if (center - Point3::new(4., 0.2, 0.)).magnitude() > 0.9 {
-                let (_sphere_material, obj): (Arc<dyn material::Material>, Arc<dyn Hittable>) =
-                    if choose_mat < 0.8 {
-                        // diffuse
-                        let albedo = crate::vec3::mul_elemwise(
-                            &crate::vec3::random_in_unit_sphere(),
-                            &crate::vec3::random_in_unit_sphere(),
-                        );
-                        let mat = Arc::new(material::Lambertian::from_color(albedo));
-                        let sph = Arc::new(Sphere::new(center, 0.2, mat.clone()));
-                        (mat, sph)
-                    } else if choose_mat < 0.95 {
-                        // metal
-                        let albedo = crate::vec3::random_in_unit_sphere();
-                        let fuzz: f64 = rng.gen_range(0.5..1.);
-                        let mat = Arc::new(material::Metal::new(albedo, fuzz));
-                        let sph = Arc::new(Sphere::new(center, 0.2, mat.clone()));
-                        (mat, sph)
-                    } else {
-                        // glass
-                        let mat = Arc::new(material::Dielectric::new(1.5));
-                        let sph = Arc::new(Sphere::new(center, 0.2, mat.clone()));
-                        (mat, sph)
-                    };
-                world.add(obj);
+            ];
+
+            if (Vec3::from(center) - Vec3::new(4., 0.2, 0.)).magnitude() > 0.9 {
+                let mut center1 = None;
+                let material = if choose_mat < 0.8 {
+                    // diffuse
+                    let albedo = crate::vec3::mul_elemwise(
+                        &Vec3::from_fn(|_, _| rng.gen()),
+                        &Vec3::from_fn(|_, _| rng.gen()),
+                    );
+                    if bouncing {
+                        center1 = Some([center[0], center[1] + rng.gen_range(0.0..0.5), center[2]]);
+                    }
+                    MaterialDesc::Lambertian {
+                        albedo: TextureRef::Color(to_array(albedo)),
+                    }
+                } else if choose_mat < 0.95 {
+                    // metal
+                    let albedo = Vec3::from_fn(|_, _| rng.gen_range(0.5..1.));
+                    MaterialDesc::Metal {
+                        albedo: TextureRef::Color(to_array(albedo)),
+                        fuzz: rng.gen_range(0.5..1.),
+                    }
+                } else {
+                    // glass
+                    MaterialDesc::Dielectric { ir: 1.5 }
+                };
+                let material = MaterialRef::Inline(material);
+                scene.objects.push(match center1 {
+                    Some(center1) => ObjectDesc::MovingSphere {
+                        center0: center,
+                        center1,
+                        time0: 0.,
+                        time1: 1.,
+                        radius: 0.2,
+                        material,
+                    },
+                    None => ObjectDesc::Sphere {
+                        center,
+                        radius: 0.2,
+                        material,
+                    },
+                });
             }
         }
     }
 
-    let m1 = Arc::new(material::Dielectric::new(1.5));
-    let m2 = Arc::new(material::Lambertian::from_color(Color::new(0.4, 0.2, 0.1)));
-    let m3 = Arc::new(material::Metal::new(Color::new(0.7, 0.6, 0.5), 0.));
+    scene.objects.push(ObjectDesc::Sphere {
+        center: [0., 1., 0.],
+        radius: 1.,
+        material: MaterialRef::Inline(MaterialDesc::Dielectric { ir: 1.5 }),
+    });
+    scene.objects.push(ObjectDesc::Sphere {
+        center: [-4., 1., 0.],
+        radius: 1.,
+        material: MaterialRef::Inline(MaterialDesc::Lambertian {
+            albedo: TextureRef::Color([0.4, 0.2, 0.1]),
+        }),
+    });
+    scene.objects.push(ObjectDesc::Sphere {
+        center: [0.7, 0.6, 0.5],
+        radius: 1.,
+        material: MaterialRef::Inline(MaterialDesc::Metal {
+            albedo: TextureRef::Color([0.7, 0.6, 0.5]),
+            fuzz: 0.,
+        }),
+    });
+
+    scene
+}
+
+// the classic cornell box, lit by a single area light in the ceiling
+pub fn cornell_box() -> Scene {
+    let mut scene = cornell_room(([213., 554., 227.], [343., 554., 332.]), 15.);
+    scene.objects.extend(cornell_blocks());
+    scene
+}
+
+// the same room with a bigger, dimmer light, and the blocks turned to smoke, one dark and one
+// light
+pub fn cornell_smoke() -> Scene {
+    let mut scene = cornell_room(([113., 554., 127.], [443., 554., 432.]), 7.);
+    let colors = vec![[0., 0., 0.], [1., 1., 1.]];
+    for (block, color) in cornell_blocks().into_iter().zip(colors) {
+        scene.objects.push(ObjectDesc::ConstantMedium {
+            boundary: ShapeRef::Inline(Box::new(block)),
+            density: 0.01,
+            albedo: TextureRef::Color(color),
+        });
+    }
+    scene
+}
+
+// walls, camera, and a square light in the ceiling
+fn cornell_room(light: ([f64; 3], [f64; 3]), brightness: f64) -> Scene {
+    let mut scene = Scene {
+        image: ImageSettings {
+            width: 600,
+            aspect_ratio: 1.,
+            samples_per_pixel: 200,
+        },
+        camera: Some(CameraSettings {
+            lookfrom: [278., 278., -800.],
+            lookat: [278., 278., 0.],
+            vfov: 40.,
+            aperture: 0.,
+            ..Default::default()
+        }),
+        background: Some(BackgroundDesc::Color([0., 0., 0.])),
+        ..Default::default()
+    };
 
-    world.add(Arc::new(Sphere::new(Point3::new(0., 1., 0.), 1., m1)));
+    let lambertian = |c| MaterialDesc::Lambertian {
+        albedo: TextureRef::Color(c),
+    };
+    scene
+        .materials
+        .insert("red".to_string(), lambertian([0.65, 0.05, 0.05]));
+    scene
+        .materials
+        .insert("white".to_string(), lambertian([0.73, 0.73, 0.73]));
+    scene
+        .materials
+        .insert("green".to_string(), lambertian([0.12, 0.45, 0.15]));
+    scene.materials.insert(
+        "light".to_string(),
+        MaterialDesc::DiffuseLight {
+            emit: TextureRef::Color([brightness; 3]),
+        },
+    );
 
-    world.add(Arc::new(Sphere::new(Point3::new(-4., 1., 0.), 1., m2)));
+    let rects = [
+        // walls: left, right, floor, ceiling, back
+        ([555., 0., 0.], [555., 555., 555.], "green"),
+        ([0., 0., 0.], [0., 555., 555.], "red"),
+        ([0., 0., 0.], [555., 0., 555.], "white"),
+        ([0., 555., 0.], [555., 555., 555.], "white"),
+        ([0., 0., 555.], [555., 555., 555.], "white"),
+        // the light, just below the ceiling
+        (light.0, light.1, "light"),
+    ];
+    for &(min, max, material) in rects.iter() {
+        scene.objects.push(ObjectDesc::Rect {
+            min,
+            max,
+            material: MaterialRef::Named(material.to_string()),
+        });
+    }
 
-    world.add(Arc::new(Sphere::new(Point3::new(0.7, 0.6, 0.5), 1., m3)));
+    scene
+}
 
-    world
+// two white boxes, turned a little
+fn cornell_blocks() -> Vec<ObjectDesc> {
+    let block = |size, angle, offset| ObjectDesc::Instance {
+        shape: ShapeRef::Inline(Box::new(ObjectDesc::Box {
+            min: [0., 0., 0.],
+            max: size,
+            material: MaterialRef::Named("white".to_string()),
+        })),
+        transform: vec![
+            TransformOp::Rotate {
+                axis: [0., 1., 0.],
+                angle,
+            },
+            TransformOp::Translate(offset),
+        ],
+    };
+    vec![
+        block([165., 330., 165.], 15., [265., 0., 295.]),
+        block([165., 165., 165.], -18., [130., 0., 65.]),
+    ]
 }
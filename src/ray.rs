@@ -1,13 +1,19 @@
 use crate::vec3::*;
 
+#[derive(Clone)]
 pub struct Ray {
     origin: Point3,
     direction: Vec3,
+    time: f64, // when the ray was sent, within the camera's shutter interval
 }
 
 impl Ray {
-    pub fn new(origin: Point3, direction: Vec3) -> Self {
-        Self { origin, direction }
+    pub fn new(origin: Point3, direction: Vec3, time: f64) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn direction(&self) -> Vec3 {
@@ -16,6 +22,9 @@ impl Ray {
     pub fn origin(&self) -> Vec3 {
         self.origin
     }
+    pub fn time(&self) -> f64 {
+        self.time
+    }
 
     // point along the ray
     pub fn at(&self, t: f64) -> Point3 {
@@ -0,0 +1,94 @@
+// where the random numbers used while rendering come from. by default that's thread_rng, but a
+// thread can put a sampler of its own in for a while, as metropolis light transport does to
+// replay paths and make small changes to them
+use rand::prelude::*;
+use std::any::Any;
+use std::cell::RefCell;
+use std::ops::Range;
+
+pub trait Sampler: Any {
+    // uniform in [0, 1)
+    fn next(&mut self) -> f64;
+}
+
+thread_local! {
+    static SAMPLER: RefCell<Option<Box<dyn Sampler>>> = const { RefCell::new(None) };
+}
+
+// runs f with sampler giving this thread's random numbers, then hands the sampler back
+pub fn with_sampler<S: Sampler, R>(sampler: S, f: impl FnOnce() -> R) -> (S, R) {
+    let previous = SAMPLER.with(|s| s.borrow_mut().replace(Box::new(sampler)));
+    let mut guard = Restore(Some(previous));
+    let result = f();
+    let sampler: Box<dyn Any> = guard.restore().expect("sampler went missing");
+    let sampler = sampler.downcast::<S>().expect("sampler was swapped");
+    (*sampler, result)
+}
+
+// puts the previous sampler back, even if f panics, so the thread doesn't keep using ours
+struct Restore(Option<Option<Box<dyn Sampler>>>);
+
+impl Restore {
+    // -> the sampler that was in use
+    fn restore(&mut self) -> Option<Box<dyn Sampler>> {
+        let previous = self.0.take().expect("sampler already restored");
+        SAMPLER.with(|s| std::mem::replace(&mut *s.borrow_mut(), previous))
+    }
+}
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if self.0.is_some() {
+            self.restore();
+        }
+    }
+}
+
+// in [0, 1)
+pub fn random() -> f64 {
+    SAMPLER.with(|s| match &mut *s.borrow_mut() {
+        Some(sampler) => sampler.next(),
+        None => thread_rng().gen(),
+    })
+}
+
+pub fn random_range(range: Range<f64>) -> f64 {
+    range.start + (range.end - range.start) * random()
+}
+
+// in 0..n
+pub fn random_index(n: usize) -> usize {
+    ((random() * n as f64) as usize).min(n - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Constant(f64);
+
+    impl Sampler for Constant {
+        fn next(&mut self) -> f64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn hands_the_sampler_back() {
+        let (sampler, x) = with_sampler(Constant(0.25), || {
+            let (_, inner) = with_sampler(Constant(0.75), random);
+            assert_eq!(inner, 0.75);
+            random()
+        });
+        assert_eq!((sampler.0, x), (0.25, 0.25));
+        assert_ne!(random(), 0.25);
+    }
+
+    #[test]
+    fn panics_put_the_previous_one_back() {
+        let caught =
+            std::panic::catch_unwind(|| with_sampler(Constant(0.5), || -> f64 { panic!("oops") }));
+        assert!(caught.is_err());
+        assert!(SAMPLER.with(|s| s.borrow().is_none()));
+    }
+}
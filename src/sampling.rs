@@ -0,0 +1,191 @@
+// piecewise-constant distributions, for importance sampling tabulated functions like the
+// brightness of an image. after pbrt's Distribution1D and Distribution2D
+
+pub struct Distribution1D {
+    func: Vec<f64>,
+    cdf: Vec<f64>, // func.len() + 1 entries, from 0 to 1
+    integral: f64,
+}
+
+impl Distribution1D {
+    // func has to be non-negative; if it's zero everywhere the distribution is uniform
+    pub fn new(func: Vec<f64>) -> Self {
+        assert!(!func.is_empty(), "distribution needs at least one value");
+        let n = func.len() as f64;
+        let mut cdf = Vec::with_capacity(func.len() + 1);
+        cdf.push(0.);
+        for (i, f) in func.iter().enumerate() {
+            cdf.push(cdf[i] + f / n);
+        }
+        let integral = cdf[func.len()];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if integral > 0. {
+                *c / integral
+            } else {
+                i as f64 / n
+            };
+        }
+        Self {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    // u in [0, 1) -> (x in [0, 1), pdf of x, index of the piece x is in)
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // the last cdf entry not above u. pieces with zero probability have no width in the
+        // cdf, so they're never the one picked
+        let i = (self.cdf.partition_point(|&c| c <= u) - 1).min(self.count() - 1);
+        let width = self.cdf[i + 1] - self.cdf[i];
+        let du = if width > 0. {
+            (u - self.cdf[i]) / width
+        } else {
+            0.
+        };
+        ((i as f64 + du) / self.count() as f64, self.pdf(i), i)
+    }
+
+    // density of the piece with the given index
+    pub fn pdf(&self, i: usize) -> f64 {
+        if self.integral > 0. {
+            self.func[i] / self.integral
+        } else {
+            1.
+        }
+    }
+}
+
+// over [0, 1]^2: the marginal picks a row (v), then that row's conditional picks u
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    // func is row by row, with width values in each
+    pub fn new(func: &[f64], width: usize) -> Self {
+        let conditional: Vec<_> = func
+            .chunks_exact(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|c| c.integral()).collect());
+        Self {
+            conditional,
+            marginal,
+        }
+    }
+
+    // -> ((u, v), pdf)
+    pub fn sample(&self, u0: f64, u1: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u1);
+        let (u, pdf_u, _) = self.conditional[row].sample(u0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let clamp = |x: f64, n: usize| ((x * n as f64) as usize).min(n - 1);
+        let row = clamp(v, self.marginal.count());
+        let col = clamp(u, self.conditional[row].count());
+        self.marginal.pdf(row) * self.conditional[row].pdf(col)
+    }
+}
+
+// how much of a sample to keep when two strategies could have produced it, given the pdf of
+// the one that did and of the other one. the weights of both add up to one
+#[derive(Clone, Copy, Debug)]
+pub enum Heuristic {
+    Balance,
+    Power,
+}
+
+impl Heuristic {
+    pub fn weight(self, pdf: f64, other: f64) -> f64 {
+        match self {
+            Heuristic::Balance => pdf / (pdf + other),
+            Heuristic::Power => pdf * pdf / (pdf * pdf + other * other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // evenly spread numbers in [0, 1), so counts come out close to exact
+    fn stratified(n: usize) -> impl Iterator<Item = f64> {
+        (0..n).map(move |i| (i as f64 + 0.5) / n as f64)
+    }
+
+    #[test]
+    fn samples_1d_in_proportion() {
+        let func = vec![1., 0., 3., 4., 0.];
+        let d = Distribution1D::new(func.clone());
+        assert_eq!(d.integral(), 8. / 5.);
+        let n = 10_000;
+        let mut counts = vec![0; func.len()];
+        for u in stratified(n) {
+            let (x, pdf, i) = d.sample(u);
+            assert!((0. ..1.).contains(&x));
+            assert_eq!(i, (x * func.len() as f64) as usize);
+            assert_eq!(pdf, d.pdf(i));
+            counts[i] += 1;
+        }
+        for (i, f) in func.iter().enumerate() {
+            let expected = f / 8. * n as f64;
+            assert!((counts[i] as f64 - expected).abs() <= 1., "{:?}", counts);
+        }
+        // a density over [0, 1]
+        let total: f64 = (0..func.len()).map(|i| d.pdf(i) / func.len() as f64).sum();
+        assert!((total - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn all_zero_is_uniform() {
+        let d = Distribution1D::new(vec![0.; 4]);
+        assert_eq!(d.integral(), 0.);
+        for u in stratified(8) {
+            let (x, pdf, i) = d.sample(u);
+            assert_eq!(pdf, 1.);
+            assert!((x - u).abs() < 1e-12);
+            assert_eq!(i, (u * 4.) as usize);
+        }
+    }
+
+    #[test]
+    fn samples_2d_in_proportion() {
+        let width = 3;
+        let func = [1., 2., 0., 0., 0., 0., 4., 1., 1.];
+        let d = Distribution2D::new(&func, width);
+        let n = 300;
+        let mut counts = [0; 9];
+        for u1 in stratified(n) {
+            for u0 in stratified(n) {
+                let ((u, v), pdf) = d.sample(u0, u1);
+                assert!((pdf - d.pdf(u, v)).abs() < 1e-12, "{} at {:?}", pdf, (u, v));
+                assert!(pdf > 0.);
+                counts[(v * 3.) as usize * width + (u * 3.) as usize] += 1;
+            }
+        }
+        let sum: f64 = func.iter().sum();
+        for (k, f) in func.iter().enumerate() {
+            let expected = f / sum * (n * n) as f64;
+            assert!(
+                (counts[k] as f64 - expected).abs() <= n as f64,
+                "{:?}",
+                counts
+            );
+            // pdf is the density over [0, 1]^2, and there are nine cells
+            let (u, v) = (((k % 3) as f64 + 0.5) / 3., ((k / 3) as f64 + 0.5) / 3.);
+            assert!((d.pdf(u, v) - f / sum * 9.).abs() < 1e-12);
+        }
+    }
+}
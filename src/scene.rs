@@ -0,0 +1,895 @@
+use crate::background::{Background, EnvironmentMap};
+use crate::camera::Camera;
+use crate::hittable::{
+    AaRect, BoxShape, ConstantMedium, Hittable, HittableList, Lights, LinearBvh, MovingSphere,
+    Sphere, Transform, Triangle, TriangleMesh,
+};
+use crate::loader;
+use crate::material::{self, Material};
+use crate::texture::{self, Texture};
+use crate::vec3::Vec3;
+
+use anyhow::{anyhow, bail, Context, Result};
+use nalgebra::{Matrix4, Rotation3, Unit};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// declarative scene description, read from toml. see scenes/*.toml for examples.
+// textures and materials can be defined once under a name and referred to by it, or written
+// inline where they're used.
+
+#[derive(Debug, Default)]
+pub struct Scene {
+    pub image: ImageSettings,
+    // when missing, a camera from a gltf model is used, and then the default camera
+    pub camera: Option<CameraSettings>,
+    // what rays escaping the scene see; the sky gradient when missing
+    pub background: Option<BackgroundDesc>,
+    pub textures: BTreeMap<String, TextureDesc>,
+    pub materials: BTreeMap<String, MaterialDesc>,
+    // objects that aren't in the scene themselves, but can be placed any number of times
+    pub shapes: BTreeMap<String, ObjectDesc>,
+    pub objects: Vec<ObjectDesc>,
+
+    // relative paths in the scene are resolved against this
+    pub base_dir: PathBuf,
+}
+
+// serde loses track of where it is inside internally tagged enums, so those get parsed one by
+// one from this, to name the offending entry in errors
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawScene {
+    #[serde(default)]
+    image: ImageSettings,
+    camera: Option<CameraSettings>,
+    background: Option<BackgroundDesc>,
+    #[serde(default)]
+    textures: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    materials: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    shapes: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    objects: Vec<toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct ImageSettings {
+    pub width: u32,
+    pub aspect_ratio: f64,
+    pub samples_per_pixel: u32,
+}
+
+impl Default for ImageSettings {
+    fn default() -> Self {
+        Self {
+            width: 1200,
+            aspect_ratio: 16. / 9.,
+            samples_per_pixel: 500,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct CameraSettings {
+    pub lookfrom: [f64; 3],
+    pub lookat: [f64; 3],
+    pub vup: [f64; 3],
+    pub vfov: f64, // vertical, in degrees
+    pub aperture: f64,
+    pub focus_dist: f64,
+    // rays are spread over [open, close] in time, for motion blur
+    pub shutter: [f64; 2],
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            lookfrom: [13., 2., 3.],
+            lookat: [0., 0., 0.],
+            vup: [0., 1., 0.],
+            vfov: 20.,
+            aperture: 0.1,
+            focus_dist: 10.,
+            shutter: [0., 0.],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum BackgroundDesc {
+    Color([f64; 3]),
+    Environment(EnvironmentDesc),
+}
+
+// an equirectangular .hdr or .exr image
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EnvironmentDesc {
+    pub path: PathBuf,
+    // about the vertical axis, in degrees
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default = "default_intensity")]
+    pub intensity: f64,
+}
+
+fn default_intensity() -> f64 {
+    1.
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TextureRef {
+    Color([f64; 3]),
+    Named(String),
+    Inline(Box<TextureDesc>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TextureDesc {
+    Solid {
+        color: [f64; 3],
+    },
+    // cubes in space, of the given edge length
+    Checker {
+        odd: TextureRef,
+        even: TextureRef,
+        #[serde(default = "default_checker_size")]
+        size: f64,
+    },
+    // tiles in uv space, this many across in u and in v
+    UvChecker {
+        odd: TextureRef,
+        even: TextureRef,
+        tiles: [u32; 2],
+    },
+    Image {
+        path: PathBuf,
+        #[serde(default = "default_wrap")]
+        wrap: texture::Wrap,
+        #[serde(default = "default_filter")]
+        filter: texture::Filter,
+        // off for images that hold data rather than colors
+        #[serde(default = "default_srgb")]
+        srgb: bool,
+    },
+    // perlin noise based; the same seed gives the same pattern
+    Noise {
+        scale: f64,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        seed: u64,
+    },
+    Marble {
+        scale: f64,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        seed: u64,
+    },
+    Wood {
+        light: [f64; 3],
+        dark: [f64; 3],
+        scale: f64,
+        #[serde(default = "default_octaves")]
+        octaves: u32,
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+// the cells of the sine checker this replaced, so scenes without a size look as they did
+pub(crate) fn default_checker_size() -> f64 {
+    std::f64::consts::PI / 10.
+}
+
+fn default_octaves() -> u32 {
+    7
+}
+
+fn default_wrap() -> texture::Wrap {
+    texture::Wrap::Repeat
+}
+
+fn default_filter() -> texture::Filter {
+    texture::Filter::Bilinear
+}
+
+fn default_srgb() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MaterialRef {
+    Named(String),
+    Inline(MaterialDesc),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDesc {
+    Lambertian { albedo: TextureRef },
+    Metal { albedo: TextureRef, fuzz: f64 },
+    Dielectric { ir: f64 },
+    DiffuseLight { emit: TextureRef },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDesc {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: MaterialRef,
+    },
+    // moves from center0 to center1 over [time0, time1]
+    MovingSphere {
+        center0: [f64; 3],
+        center1: [f64; 3],
+        time0: f64,
+        time1: f64,
+        radius: f64,
+        material: MaterialRef,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        material: MaterialRef,
+    },
+    Mesh {
+        positions: Vec<[f64; 3]>,
+        normals: Option<Vec<[f64; 3]>>,
+        uvs: Option<Vec<[f64; 2]>>,
+        indices: Vec<[usize; 3]>,
+        material: MaterialRef,
+    },
+    // axis-aligned; min and max must agree on exactly one axis, which the rect faces along
+    Rect {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: MaterialRef,
+    },
+    Box {
+        min: [f64; 3],
+        max: [f64; 3],
+        material: MaterialRef,
+    },
+    // a shape placed by a list of transforms, applied in order
+    Instance {
+        shape: ShapeRef,
+        transform: Vec<TransformOp>,
+    },
+    // fog or smoke filling a closed shape
+    ConstantMedium {
+        boundary: ShapeRef,
+        density: f64,
+        albedo: TextureRef,
+    },
+    // an .obj, .ply, .gltf or .glb file, with the materials it brings along
+    Model {
+        path: PathBuf,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ShapeRef {
+    Named(String),
+    Inline(Box<ObjectDesc>),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum TransformOp {
+    Translate([f64; 3]),
+    // about an axis through the origin, in degrees
+    Rotate { axis: [f64; 3], angle: f64 },
+    Scale([f64; 3]),
+    // any affine matrix, row by row
+    Matrix([[f64; 4]; 4]),
+}
+
+impl TransformOp {
+    fn matrix(&self) -> Result<Matrix4<f64>> {
+        Ok(match self {
+            TransformOp::Translate(offset) => Matrix4::new_translation(&vec3(*offset)),
+            TransformOp::Rotate { axis, angle } => {
+                let axis = Unit::try_new(vec3(*axis), 1e-12)
+                    .ok_or_else(|| anyhow!("in rotate: axis can't be zero"))?;
+                Rotation3::from_axis_angle(&axis, angle.to_radians()).to_homogeneous()
+            }
+            TransformOp::Scale(factors) => Matrix4::new_nonuniform_scaling(&vec3(*factors)),
+            TransformOp::Matrix(rows) => {
+                if rows[3] != [0., 0., 0., 1.] {
+                    bail!("in matrix: last row must be [0, 0, 0, 1]");
+                }
+                Matrix4::from_fn(|r, c| rows[r][c])
+            }
+        })
+    }
+}
+
+fn vec3(v: [f64; 3]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+impl Scene {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&src)
+            .map(|scene| Self {
+                base_dir: path.parent().unwrap_or_else(|| Path::new("")).to_owned(),
+                ..scene
+            })
+            .with_context(|| format!("failed to parse {}", path.display()))
+    }
+
+    fn parse(src: &str) -> Result<Self> {
+        let raw: RawScene = toml::from_str(src)?;
+        // the image height is worked out from it
+        let aspect_ratio = raw.image.aspect_ratio;
+        if !(aspect_ratio.is_finite() && aspect_ratio > 0.) {
+            bail!("in image: in aspect_ratio: must be positive");
+        }
+        let textures = raw
+            .textures
+            .into_iter()
+            .map(|(name, v)| {
+                let t = v
+                    .try_into()
+                    .with_context(|| format!("in textures.{}", name))?;
+                Ok((name, t))
+            })
+            .collect::<Result<_>>()?;
+        let materials = raw
+            .materials
+            .into_iter()
+            .map(|(name, v)| {
+                let m = v
+                    .try_into()
+                    .with_context(|| format!("in materials.{}", name))?;
+                Ok((name, m))
+            })
+            .collect::<Result<_>>()?;
+        let shapes = raw
+            .shapes
+            .into_iter()
+            .map(|(name, v)| {
+                let o = v
+                    .try_into()
+                    .with_context(|| format!("in shapes.{}", name))?;
+                Ok((name, o))
+            })
+            .collect::<Result<_>>()?;
+        let objects = raw
+            .objects
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| v.try_into().with_context(|| format!("in objects[{}]", i)))
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            image: raw.image,
+            camera: raw.camera,
+            background: raw.background,
+            textures,
+            materials,
+            shapes,
+            objects,
+            base_dir: PathBuf::new(),
+        })
+    }
+
+    // a scene holding just one model file, seen from the default camera
+    pub fn from_model<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            objects: vec![ObjectDesc::Model {
+                path: path.as_ref().to_owned(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    // the world, the lights in it to sample directly, and the camera
+    pub fn build(&self) -> Result<(HittableList, Lights, Camera)> {
+        let mut builder = Builder {
+            scene: self,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            shapes: HashMap::new(),
+            resolving: vec![],
+        };
+
+        let mut world = HittableList::default();
+        let mut lights = Lights::default();
+        let mut model_camera = None;
+        for (i, object) in self.objects.iter().enumerate() {
+            let mut added = HittableList::default();
+            let camera = builder
+                .add_object(object, &mut added)
+                .with_context(|| format!("in objects[{}]", i))?;
+            // every part of it that gives off light is sampled as a light, wherever it came from
+            for o in added.into_inner() {
+                world.add(if o.is_emissive() { lights.add(o) } else { o });
+            }
+            model_camera = model_camera.or(camera);
+        }
+
+        let camera = match (&self.camera, model_camera) {
+            (Some(c), _) => self.camera(c),
+            (None, Some(c)) => c,
+            (None, None) => self.camera(&CameraSettings::default()),
+        };
+        Ok((world, lights, camera))
+    }
+
+    pub fn background(&self) -> Result<Background> {
+        Ok(match &self.background {
+            Some(BackgroundDesc::Color(c)) => Background::Color(vec3(*c)),
+            Some(BackgroundDesc::Environment(env)) => Background::Environment(
+                EnvironmentMap::open(self.base_dir.join(&env.path))
+                    .context("in background")?
+                    .with_rotation(env.rotation)
+                    .with_intensity(env.intensity),
+            ),
+            None => Background::Sky,
+        })
+    }
+
+    fn camera(&self, c: &CameraSettings) -> Camera {
+        Camera::new(
+            vec3(c.lookfrom),
+            vec3(c.lookat),
+            vec3(c.vup),
+            c.vfov,
+            self.image.aspect_ratio,
+            c.aperture,
+            c.focus_dist,
+        )
+        .with_shutter(c.shutter[0], c.shutter[1])
+    }
+}
+
+// builds named textures and materials on first use, so each is shared by everything naming it
+struct Builder<'a> {
+    scene: &'a Scene,
+    textures: HashMap<&'a str, Arc<dyn Texture>>,
+    materials: HashMap<&'a str, Arc<dyn Material>>,
+    shapes: HashMap<&'a str, Arc<dyn Hittable>>,
+    // the names being built right now, outermost first, so a name that needs itself is caught
+    resolving: Vec<String>,
+}
+
+impl<'a> Builder<'a> {
+    fn enter(&mut self, key: String) -> Result<()> {
+        if let Some(start) = self.resolving.iter().position(|k| *k == key) {
+            let mut cycle = self.resolving[start..].to_vec();
+            cycle.push(key);
+            bail!("{} refers to itself: {}", cycle[0], cycle.join(" -> "));
+        }
+        self.resolving.push(key);
+        Ok(())
+    }
+    fn texture_ref(&mut self, r: &'a TextureRef) -> Result<Arc<dyn Texture>> {
+        match r {
+            TextureRef::Color(c) => Ok(Arc::new(texture::SolidColor::new(vec3(*c)))),
+            TextureRef::Inline(desc) => self.texture(desc),
+            TextureRef::Named(name) => {
+                if let Some(t) = self.textures.get(name.as_str()) {
+                    return Ok(Arc::clone(t));
+                }
+                let desc = self
+                    .scene
+                    .textures
+                    .get(name)
+                    .ok_or_else(|| anyhow!("unknown texture {:?}", name))?;
+                self.enter(format!("textures.{}", name))?;
+                let t = self
+                    .texture(desc)
+                    .with_context(|| format!("in textures.{}", name));
+                self.resolving.pop();
+                let t = t?;
+                self.textures.insert(name, Arc::clone(&t));
+                Ok(t)
+            }
+        }
+    }
+
+    fn texture(&mut self, desc: &'a TextureDesc) -> Result<Arc<dyn Texture>> {
+        Ok(match desc {
+            TextureDesc::Solid { color } => Arc::new(texture::SolidColor::new(vec3(*color))),
+            TextureDesc::Checker { odd, even, size } => {
+                if *size <= 0. {
+                    bail!("in size: must be positive");
+                }
+                Arc::new(texture::SolidChecker::new(
+                    self.texture_ref(odd).context("in odd")?,
+                    self.texture_ref(even).context("in even")?,
+                    *size,
+                ))
+            }
+            TextureDesc::UvChecker { odd, even, tiles } => Arc::new(texture::UvChecker::new(
+                self.texture_ref(odd).context("in odd")?,
+                self.texture_ref(even).context("in even")?,
+                tiles[0],
+                tiles[1],
+            )),
+            TextureDesc::Image {
+                path,
+                wrap,
+                filter,
+                srgb,
+            } => Arc::new(
+                texture::ImageTexture::open(self.scene.base_dir.join(path))?
+                    .with_wrap(*wrap, *wrap)
+                    .with_filter(*filter)
+                    .with_srgb(*srgb),
+            ),
+            TextureDesc::Noise {
+                scale,
+                octaves,
+                seed,
+            } => Arc::new(texture::Noise::new(
+                texture::Perlin::new(*seed),
+                *scale,
+                *octaves,
+            )),
+            TextureDesc::Marble {
+                scale,
+                octaves,
+                seed,
+            } => Arc::new(texture::Marble::new(
+                texture::Perlin::new(*seed),
+                *scale,
+                *octaves,
+            )),
+            TextureDesc::Wood {
+                light,
+                dark,
+                scale,
+                octaves,
+                seed,
+            } => Arc::new(texture::Wood::new(
+                texture::Perlin::new(*seed),
+                vec3(*light),
+                vec3(*dark),
+                *scale,
+                *octaves,
+            )),
+        })
+    }
+
+    fn material_ref(&mut self, r: &'a MaterialRef) -> Result<Arc<dyn Material>> {
+        match r {
+            MaterialRef::Inline(desc) => self.material(desc),
+            MaterialRef::Named(name) => {
+                if let Some(m) = self.materials.get(name.as_str()) {
+                    return Ok(Arc::clone(m));
+                }
+                let desc = self
+                    .scene
+                    .materials
+                    .get(name)
+                    .ok_or_else(|| anyhow!("unknown material {:?}", name))?;
+                self.enter(format!("materials.{}", name))?;
+                let m = self
+                    .material(desc)
+                    .with_context(|| format!("in materials.{}", name));
+                self.resolving.pop();
+                let m = m?;
+                self.materials.insert(name, Arc::clone(&m));
+                Ok(m)
+            }
+        }
+    }
+
+    fn material(&mut self, desc: &'a MaterialDesc) -> Result<Arc<dyn Material>> {
+        Ok(match desc {
+            MaterialDesc::Lambertian { albedo } => Arc::new(material::Lambertian::new(
+                self.texture_ref(albedo).context("in albedo")?,
+            )),
+            MaterialDesc::Metal { albedo, fuzz } => Arc::new(material::Metal::new(
+                self.texture_ref(albedo).context("in albedo")?,
+                *fuzz,
+            )),
+            MaterialDesc::Dielectric { ir } => Arc::new(material::Dielectric::new(*ir)),
+            MaterialDesc::DiffuseLight { emit } => Arc::new(material::DiffuseLight::new(
+                self.texture_ref(emit).context("in emit")?,
+            )),
+        })
+    }
+
+    fn shape_ref(&mut self, r: &'a ShapeRef) -> Result<Arc<dyn Hittable>> {
+        match r {
+            ShapeRef::Inline(desc) => self.shape(desc),
+            ShapeRef::Named(name) => {
+                if let Some(s) = self.shapes.get(name.as_str()) {
+                    return Ok(Arc::clone(s));
+                }
+                let desc = self
+                    .scene
+                    .shapes
+                    .get(name)
+                    .ok_or_else(|| anyhow!("unknown shape {:?}", name))?;
+                self.enter(format!("shapes.{}", name))?;
+                let s = self
+                    .shape(desc)
+                    .with_context(|| format!("in shapes.{}", name));
+                self.resolving.pop();
+                let s = s?;
+                self.shapes.insert(name, Arc::clone(&s));
+                Ok(s)
+            }
+        }
+    }
+
+    // a whole object as one hittable; anything made of several parts gets its own bvh, which
+    // all instances of it share
+    fn shape(&mut self, desc: &'a ObjectDesc) -> Result<Arc<dyn Hittable>> {
+        let mut list = HittableList::default();
+        self.add_object(desc, &mut list)?;
+        let mut objects = list.into_inner();
+        match objects.len() {
+            0 => bail!("shape is empty"),
+            1 => Ok(objects.remove(0)),
+            _ => Ok(Arc::new(LinearBvh::from_hittable_list(HittableList::new(
+                objects,
+            ))?)),
+        }
+    }
+
+    // returns the camera of a gltf model, if it has one
+    fn add_object(
+        &mut self,
+        object: &'a ObjectDesc,
+        world: &mut HittableList,
+    ) -> Result<Option<Camera>> {
+        match object {
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material,
+            } => {
+                let material = self.material_ref(material).context("in material")?;
+                world.add(Arc::new(Sphere::new(vec3(*center), *radius, material)));
+            }
+            ObjectDesc::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => {
+                let material = self.material_ref(material).context("in material")?;
+                world.add(Arc::new(MovingSphere::new(
+                    vec3(*center0),
+                    vec3(*center1),
+                    *time0,
+                    *time1,
+                    *radius,
+                    material,
+                )));
+            }
+            ObjectDesc::Triangle { vertices, material } => {
+                let material = self.material_ref(material).context("in material")?;
+                let [p0, p1, p2] = *vertices;
+                world.add(Arc::new(Triangle::new(
+                    vec3(p0),
+                    vec3(p1),
+                    vec3(p2),
+                    material,
+                )));
+            }
+            ObjectDesc::Mesh {
+                positions,
+                normals,
+                uvs,
+                indices,
+                material,
+            } => {
+                let material = self.material_ref(material).context("in material")?;
+                if let Some(bad) = indices.iter().flatten().find(|&&i| i >= positions.len()) {
+                    bail!("in indices: {} is out of range", bad);
+                }
+                if normals.as_ref().is_some_and(|n| n.len() != positions.len()) {
+                    bail!("in normals: need one per position");
+                }
+                if uvs.as_ref().is_some_and(|uv| uv.len() != positions.len()) {
+                    bail!("in uvs: need one per position");
+                }
+                let mesh = TriangleMesh::new(
+                    positions.iter().map(|p| vec3(*p)).collect(),
+                    normals
+                        .as_ref()
+                        .map(|ns| ns.iter().map(|n| vec3(*n)).collect()),
+                    uvs.as_ref()
+                        .map(|uvs| uvs.iter().map(|uv| (uv[0], uv[1])).collect()),
+                    indices.clone(),
+                    material,
+                );
+                for tri in mesh.into_hittable_list().into_inner() {
+                    world.add(tri);
+                }
+            }
+            ObjectDesc::Rect { min, max, material } => {
+                let material = self.material_ref(material).context("in material")?;
+                let flat: Vec<usize> = (0..3).filter(|&a| min[a] == max[a]).collect();
+                let (x, y, z) = ((min[0], max[0]), (min[1], max[1]), (min[2], max[2]));
+                let rect = match flat[..] {
+                    [0] => AaRect::yz(y, z, min[0], material),
+                    [1] => AaRect::xz(x, z, min[1], material),
+                    [2] => AaRect::xy(x, y, min[2], material),
+                    _ => bail!("min and max must be equal on exactly one axis"),
+                };
+                world.add(Arc::new(rect));
+            }
+            ObjectDesc::Box { min, max, material } => {
+                let material = self.material_ref(material).context("in material")?;
+                world.add(Arc::new(BoxShape::new(vec3(*min), vec3(*max), material)));
+            }
+            ObjectDesc::Instance { shape, transform } => {
+                let shape = self.shape_ref(shape).context("in shape")?;
+                let mut matrix = Matrix4::identity();
+                for (i, op) in transform.iter().enumerate() {
+                    matrix = op
+                        .matrix()
+                        .with_context(|| format!("in transform[{}]", i))?
+                        * matrix;
+                }
+                if !matrix.is_invertible() {
+                    bail!("in transform: not invertible");
+                }
+                world.add(Arc::new(Transform::new(shape, matrix)));
+            }
+            ObjectDesc::ConstantMedium {
+                boundary,
+                density,
+                albedo,
+            } => {
+                let boundary = self.shape_ref(boundary).context("in boundary")?;
+                let albedo = self.texture_ref(albedo).context("in albedo")?;
+                if *density <= 0. {
+                    bail!("in density: must be positive");
+                }
+                world.add(Arc::new(ConstantMedium::new(boundary, *density, albedo)));
+            }
+            ObjectDesc::Model { path } => {
+                let path = self.scene.base_dir.join(path);
+                let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+                let (objects, camera) = match ext {
+                    "obj" => (loader::load_obj(&path)?, None),
+                    "ply" => (loader::load_ply(&path)?, None),
+                    "gltf" | "glb" => loader::load_gltf(&path, self.scene.image.aspect_ratio)?,
+                    _ => bail!("in path: unsupported model format {:?}", path.display()),
+                };
+                for o in objects.into_inner() {
+                    world.add(o);
+                }
+                return Ok(camera);
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(src: &str) -> String {
+        let err = Scene::parse(src)
+            .and_then(|scene| scene.build().map(|_| ()))
+            .unwrap_err();
+        format!("{:#}", err)
+    }
+
+    #[test]
+    fn builds() {
+        let scene = Scene::parse(include_str!("../scenes/two_spheres.toml")).unwrap();
+        let (world, _, _) = scene.build().unwrap();
+        assert_eq!(world.into_inner().len(), 2);
+    }
+
+    #[test]
+    fn parse_errors_say_where() {
+        let e = error("[textures.a]\ntype = \"checker\"\nodd = [0, 0, 0]\n");
+        assert!(
+            e.starts_with("in textures.a: missing field `even`"),
+            "{}",
+            e
+        );
+
+        let e = error("[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\n");
+        assert!(
+            e.starts_with("in objects[0]: missing field `radius`"),
+            "{}",
+            e
+        );
+
+        let e =
+            error("[[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = 1\n");
+        assert!(e.starts_with("in objects[0]: "), "{}", e);
+
+        for ratio in ["0", "-1.5", "inf", "nan"] {
+            let e = error(&format!("[image]\naspect_ratio = {}\n", ratio));
+            assert_eq!(e, "in image: in aspect_ratio: must be positive");
+        }
+    }
+
+    #[test]
+    fn build_errors_say_where() {
+        let e = error(
+            "[materials.m]\ntype = \"lambertian\"\nalbedo = \"nope\"\n\n\
+             [[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"m\"\n",
+        );
+        assert_eq!(
+            e,
+            "in objects[0]: in material: in materials.m: in albedo: unknown texture \"nope\""
+        );
+    }
+
+    #[test]
+    fn lights_wherever_they_come_from() {
+        let dir = std::env::temp_dir().join(format!("rt-scene-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("lamp.mtl"), "newmtl glow\nKe 4 4 4\n").unwrap();
+        fs::write(
+            dir.join("lamp.obj"),
+            "mtllib lamp.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nusemtl glow\nf 3 2 1\n",
+        )
+        .unwrap();
+        let src = format!(
+            "[materials.glow]\ntype = \"diffuse_light\"\nemit = [1, 1, 1]\n\n\
+             [[objects]]\ntype = \"model\"\npath = {:?}\n\n\
+             [[objects]]\ntype = \"instance\"\ntransform = [{{ translate = [0, 5, 0] }}]\n\
+             shape = {{ type = \"sphere\", center = [0, 0, 0], radius = 1, material = \"glow\" }}\n",
+            dir.join("lamp.obj")
+        );
+        let built = Scene::parse(&src).and_then(|scene| scene.build());
+        fs::remove_dir_all(&dir).unwrap();
+        let (world, lights, _) = built.unwrap();
+        assert_eq!(world.into_inner().len(), 3);
+        // the obj's glowing face and the moved sphere, but not the obj's plain face
+        let sampled: std::collections::HashSet<_> = (0..100)
+            .map(|_| lights.sample_point().unwrap().0.light().unwrap())
+            .collect();
+        assert_eq!(sampled.len(), 2);
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let e = error(
+            "[textures.a]\ntype = \"checker\"\nodd = \"b\"\neven = [1, 1, 1]\nsize = 1\n\n\
+             [textures.b]\ntype = \"checker\"\nodd = [0, 0, 0]\neven = \"a\"\nsize = 1\n\n\
+             [materials.m]\ntype = \"lambertian\"\nalbedo = \"a\"\n\n\
+             [[objects]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\nmaterial = \"m\"\n",
+        );
+        assert!(
+            e.ends_with("textures.a refers to itself: textures.a -> textures.b -> textures.a"),
+            "{}",
+            e
+        );
+
+        let e = error(
+            "[shapes.s]\ntype = \"instance\"\nshape = \"s\"\ntransform = []\n\n\
+             [[objects]]\ntype = \"instance\"\nshape = \"s\"\ntransform = []\n",
+        );
+        assert!(
+            e.ends_with("shapes.s refers to itself: shapes.s -> shapes.s"),
+            "{}",
+            e
+        );
+    }
+}
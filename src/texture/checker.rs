@@ -1,32 +1,83 @@
-use crate::texture::{SolidColor, Texture};
-use crate::vec3::{Color, Point3, Vec3};
+use crate::hittable::HitRecord;
+use crate::texture::Texture;
+use crate::vec3::{Color, Point3};
 
 use std::sync::Arc;
 
-pub struct Checker {
+// alternates between two textures on a grid of cubes in space, so it needs no uvs and looks
+// the same on any surface passing through it
+pub struct SolidChecker {
     odd: Arc<dyn Texture>,
     even: Arc<dyn Texture>,
+    inv_size: f64,
 }
 
-impl Checker {
-    pub fn new(odd: Arc<dyn Texture>, even: Arc<dyn Texture>) -> Self {
-        Self { odd, even }
-    }
-    pub fn from_colors(odd: Color, even: Color) -> Self {
+impl SolidChecker {
+    // size is the edge length of a cube
+    pub fn new(odd: Arc<dyn Texture>, even: Arc<dyn Texture>, size: f64) -> Self {
         Self {
-            odd: Arc::new(SolidColor::new(odd)),
-            even: Arc::new(SolidColor::new(even)),
+            odd,
+            even,
+            inv_size: 1. / size,
+        }
+    }
+
+    fn pick(&self, p: Point3) -> &dyn Texture {
+        let cell = (self.inv_size * p).map(|c| c.floor() as i64);
+        if (cell.x + cell.y + cell.z).rem_euclid(2) == 1 {
+            self.odd.as_ref()
+        } else {
+            self.even.as_ref()
         }
     }
 }
 
-impl Texture for Checker {
+impl Texture for SolidChecker {
     fn value(&self, u: f64, v: f64, p: Point3) -> Color {
-        let sines = (10. * p.x).sin() * (10. * p.y).sin() * (10. * p.z).sin();
-        if sines < 0. {
-            self.odd.value(u, v, p)
+        self.pick(p).value(u, v, p)
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.pick(rec.p()).value_at(rec)
+    }
+}
+
+// alternates between two textures on a grid of tiles in uv space, which follows the surface
+pub struct UvChecker {
+    odd: Arc<dyn Texture>,
+    even: Arc<dyn Texture>,
+    tiles_u: f64,
+    tiles_v: f64,
+}
+
+impl UvChecker {
+    // the number of tiles across [0, 1] in u and in v
+    pub fn new(odd: Arc<dyn Texture>, even: Arc<dyn Texture>, tiles_u: u32, tiles_v: u32) -> Self {
+        Self {
+            odd,
+            even,
+            tiles_u: tiles_u as f64,
+            tiles_v: tiles_v as f64,
+        }
+    }
+
+    fn pick(&self, u: f64, v: f64) -> &dyn Texture {
+        let i = (u * self.tiles_u).floor() as i64;
+        let j = (v * self.tiles_v).floor() as i64;
+        if (i + j).rem_euclid(2) == 1 {
+            self.odd.as_ref()
         } else {
-            self.even.value(u, v, p)
+            self.even.as_ref()
         }
     }
 }
+
+impl Texture for UvChecker {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        self.pick(u, v).value(u, v, p)
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.pick(rec.u(), rec.v()).value_at(rec)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::texture::Texture;
+use crate::vec3::{Color, Point3};
+
+use anyhow::{Context, Result};
+use image::RgbImage;
+use serde::Deserialize;
+use std::path::Path;
+
+// what happens to uv coordinates outside [0, 1]
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+pub struct ImageTexture {
+    img: RgbImage,
+    wrap_u: Wrap,
+    wrap_v: Wrap,
+    filter: Filter,
+    // 8 bit channel value -> linear intensity
+    to_linear: [f64; 256],
+}
+
+impl ImageTexture {
+    // the image is taken to be sRGB encoded, like nearly every 8 bit image file
+    pub fn new(img: RgbImage) -> Self {
+        Self {
+            img,
+            wrap_u: Wrap::Repeat,
+            wrap_v: Wrap::Repeat,
+            filter: Filter::Bilinear,
+            to_linear: [0.; 256],
+        }
+        .with_srgb(true)
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let img = image::open(path)
+            .with_context(|| format!("failed to load texture {}", path.display()))?
+            .into_rgb8();
+        Ok(Self::new(img))
+    }
+
+    pub fn with_wrap(self, wrap_u: Wrap, wrap_v: Wrap) -> Self {
+        Self {
+            wrap_u,
+            wrap_v,
+            ..self
+        }
+    }
+
+    pub fn with_filter(self, filter: Filter) -> Self {
+        Self { filter, ..self }
+    }
+
+    // false for images holding data rather than colors, which are stored linearly
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        for (i, l) in self.to_linear.iter_mut().enumerate() {
+            let c = i as f64 / 255.;
+            *l = if !srgb {
+                c
+            } else if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+        }
+        self
+    }
+
+    fn texel(&self, i: i64, j: i64) -> Color {
+        let (width, height) = self.img.dimensions();
+        let pixel = self
+            .img
+            .get_pixel(wrap(self.wrap_u, i, width), wrap(self.wrap_v, j, height));
+        Color::new(
+            self.to_linear[pixel[0] as usize],
+            self.to_linear[pixel[1] as usize],
+            self.to_linear[pixel[2] as usize],
+        )
+    }
+}
+
+// a texel index into [0, n)
+fn wrap(mode: Wrap, i: i64, n: u32) -> u32 {
+    let n = n as i64;
+    let i = match mode {
+        Wrap::Repeat => i.rem_euclid(n),
+        Wrap::Clamp => i.clamp(0, n - 1),
+        Wrap::Mirror => {
+            let i = i.rem_euclid(2 * n);
+            if i < n {
+                i
+            } else {
+                2 * n - 1 - i
+            }
+        }
+    };
+    i as u32
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Point3) -> Color {
+        let (width, height) = self.img.dimensions();
+        if width == 0 || height == 0 {
+            // debugging aid for missing texture data
+            return Color::new(0., 1., 1.);
+        }
+
+        // in texels, with v flipped to image coordinates
+        let x = u * width as f64;
+        let y = (1. - v) * height as f64;
+
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                // texel centers are at half coordinates
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (i, j) = (x.floor(), y.floor());
+                let (fx, fy) = (x - i, y - j);
+                let (i, j) = (i as i64, j as i64);
+                let top = (1. - fx) * self.texel(i, j) + fx * self.texel(i + 1, j);
+                let bottom = (1. - fx) * self.texel(i, j + 1) + fx * self.texel(i + 1, j + 1);
+                (1. - fy) * top + fy * bottom
+            }
+        }
+    }
+}
@@ -1,11 +1,26 @@
-use crate::vec3::{Color, Point3, Vec3};
+use crate::hittable::HitRecord;
+use crate::vec3::{Color, Point3};
 
 mod checker;
+mod image_texture;
+mod noise;
+mod perlin;
 mod solid_color;
+mod vertex_color;
 
-pub use crate::texture::checker::Checker;
+pub use crate::texture::checker::{SolidChecker, UvChecker};
+pub use crate::texture::image_texture::{Filter, ImageTexture, Wrap};
+pub use crate::texture::noise::{Marble, Noise, Wood};
+pub use crate::texture::perlin::Perlin;
 pub use crate::texture::solid_color::SolidColor;
+pub use crate::texture::vertex_color::VertexColor;
 
 pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: Point3) -> Color;
+
+    // the value where a ray hit, which materials look up. most textures only need the hit's
+    // uv and point, but some read more of it, like the vertex colors a mesh blends there
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        self.value(rec.u(), rec.v(), rec.p())
+    }
 }
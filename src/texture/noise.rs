@@ -0,0 +1,87 @@
+use crate::texture::{Perlin, Texture};
+use crate::vec3::{Color, Point3};
+
+use std::f64::consts::PI;
+
+// procedural textures over perlin turbulence. scale sets the feature size in world units, and
+// octaves how much fine detail there is
+
+// gray turbulence
+pub struct Noise {
+    perlin: Perlin,
+    scale: f64,
+    octaves: u32,
+}
+
+impl Noise {
+    pub fn new(perlin: Perlin, scale: f64, octaves: u32) -> Self {
+        Self {
+            perlin,
+            scale,
+            octaves,
+        }
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        Color::repeat(self.perlin.turb(&(self.scale * p), self.octaves))
+    }
+}
+
+// veins running across z, bent about by turbulence
+pub struct Marble {
+    perlin: Perlin,
+    scale: f64,
+    octaves: u32,
+}
+
+impl Marble {
+    pub fn new(perlin: Perlin, scale: f64, octaves: u32) -> Self {
+        Self {
+            perlin,
+            scale,
+            octaves,
+        }
+    }
+}
+
+impl Texture for Marble {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let p = self.scale * p;
+        let phase = p.z + 10. * self.perlin.turb(&p, self.octaves);
+        Color::repeat(0.5 * (1. + phase.sin()))
+    }
+}
+
+// growth rings around the y axis, going between a light and a dark color
+pub struct Wood {
+    perlin: Perlin,
+    light: Color,
+    dark: Color,
+    scale: f64,
+    octaves: u32,
+}
+
+impl Wood {
+    pub fn new(perlin: Perlin, light: Color, dark: Color, scale: f64, octaves: u32) -> Self {
+        Self {
+            perlin,
+            light,
+            dark,
+            scale,
+            octaves,
+        }
+    }
+}
+
+impl Texture for Wood {
+    fn value(&self, _u: f64, _v: f64, p: Point3) -> Color {
+        let p = self.scale * p;
+        let radius = (p.x * p.x + p.z * p.z).sqrt();
+        let rings = radius + 0.5 * self.perlin.turb(&p, self.octaves);
+        // sharpen so the dark rings come out thinner than the light wood between them
+        let t = (0.5 * (1. + (2. * PI * rings).sin())).powi(3);
+        self.light + t * (self.dark - self.light)
+    }
+}
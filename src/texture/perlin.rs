@@ -0,0 +1,96 @@
+use crate::vec3::{Point3, Vec3};
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+// gradient noise on the integer lattice, after ken perlin's improved noise.
+// the same seed always gives the same noise, so renders are reproducible
+const POINT_COUNT: usize = 256;
+
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                let v = Vec3::from_fn(|_, _| rng.gen_range(-1.0..1.));
+                let len = v.magnitude_squared();
+                if len > 1e-6 && len < 1. {
+                    break v.normalize();
+                }
+            })
+            .collect();
+        let mut perm = || {
+            let mut p: Vec<usize> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        Self {
+            gradients,
+            perm_x: perm(),
+            perm_y: perm(),
+            perm_z: perm(),
+        }
+    }
+
+    // in [-1, 1], and zero on lattice points
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let cell = p.map(f64::floor);
+        let f = p - cell;
+
+        let mut c = [[[Vec3::zeros(); 2]; 2]; 2];
+        for (di, plane) in c.iter_mut().enumerate() {
+            for (dj, row) in plane.iter_mut().enumerate() {
+                for (dk, g) in row.iter_mut().enumerate() {
+                    let idx = |perm: &[usize], x: f64, d: usize| {
+                        perm[(x as i64 + d as i64) as usize & (POINT_COUNT - 1)]
+                    };
+                    *g = self.gradients[idx(&self.perm_x, cell.x, di)
+                        ^ idx(&self.perm_y, cell.y, dj)
+                        ^ idx(&self.perm_z, cell.z, dk)];
+                }
+            }
+        }
+        Self::interp(&c, &f)
+    }
+
+    // sum of octaves at doubling frequencies and halving weights. taking the absolute value
+    // folds the noise over, which makes the creases that turbulence looks like
+    pub fn turb(&self, p: &Point3, octaves: u32) -> f64 {
+        let mut accum = 0.;
+        let mut p = *p;
+        let mut weight = 1.;
+        for _ in 0..octaves {
+            accum += weight * self.noise(&p);
+            weight *= 0.5;
+            p *= 2.;
+        }
+        accum.abs()
+    }
+
+    // trilinear over the cube corners, each contributing the dot of its gradient with the
+    // offset to p, weighted by hermite smoothed distances
+    fn interp(c: &[[[Vec3; 2]; 2]; 2], f: &Vec3) -> f64 {
+        let s = f.map(|t| t * t * (3. - 2. * t));
+        let mut accum = 0.;
+        for (i, plane) in c.iter().enumerate() {
+            for (j, row) in plane.iter().enumerate() {
+                for (k, g) in row.iter().enumerate() {
+                    let (i, j, k) = (i as f64, j as f64, k as f64);
+                    let weight = Vec3::new(f.x - i, f.y - j, f.z - k);
+                    accum += (i * s.x + (1. - i) * (1. - s.x))
+                        * (j * s.y + (1. - j) * (1. - s.y))
+                        * (k * s.z + (1. - k) * (1. - s.z))
+                        * g.dot(&weight);
+                }
+            }
+        }
+        accum
+    }
+}
@@ -1,5 +1,5 @@
 use crate::texture::Texture;
-use crate::vec3::{Color, Point3, Vec3};
+use crate::vec3::{Color, Point3};
 
 pub struct SolidColor {
     color_value: Color,
@@ -13,6 +13,6 @@ impl SolidColor {
 
 impl Texture for SolidColor {
     fn value(&self, _u: f64, _v: f64, _p: Point3) -> Color {
-        self.color_value.clone()
+        self.color_value
     }
 }
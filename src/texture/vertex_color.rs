@@ -0,0 +1,31 @@
+use crate::hittable::HitRecord;
+use crate::texture::Texture;
+use crate::vec3::{self, Color, Point3};
+
+use std::sync::Arc;
+
+// the colors a mesh gives its vertices, blended across each face, tinting a base texture.
+// where the hit has no colors of its own this is just the base
+pub struct VertexColor {
+    base: Arc<dyn Texture>,
+}
+
+impl VertexColor {
+    pub fn new(base: Arc<dyn Texture>) -> Self {
+        Self { base }
+    }
+}
+
+impl Texture for VertexColor {
+    fn value(&self, u: f64, v: f64, p: Point3) -> Color {
+        self.base.value(u, v, p)
+    }
+
+    fn value_at(&self, rec: &HitRecord) -> Color {
+        let base = self.base.value_at(rec);
+        match rec.color() {
+            Some(color) => vec3::mul_elemwise(&base, &color),
+            None => base,
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::sampler;
 pub use nalgebra::Unit;
 use nalgebra::Vector3;
 
@@ -6,13 +7,11 @@ pub type Vec3 = Vector3<f64>;
 pub type Point3 = Vector3<f64>;
 
 pub fn random_in_unit_sphere() -> Vec3 {
-    use rand::prelude::*;
-    let mut rng = rand::thread_rng();
     loop {
         let p = Vec3::new(
-            rng.gen_range(0.0..1.0),
-            rng.gen_range(0.0..1.0),
-            rng.gen_range(0.0..1.0),
+            sampler::random_range(-1.0..1.0),
+            sampler::random_range(-1.0..1.0),
+            sampler::random_range(-1.0..1.0),
         );
         if p.magnitude_squared() >= 1. {
             continue;
@@ -27,10 +26,12 @@ pub fn random_unit() -> Unit<Vec3> {
 }
 
 pub fn random_in_unit_disk() -> Vec3 {
-    use rand::prelude::*;
-    let mut rng = rand::thread_rng();
     loop {
-        let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.);
+        let p = Vec3::new(
+            sampler::random_range(-1.0..1.0),
+            sampler::random_range(-1.0..1.0),
+            0.,
+        );
         if p.magnitude_squared() >= 1. {
             continue;
         }
@@ -41,11 +42,11 @@ pub fn random_in_unit_disk() -> Vec3 {
 // TODO: newtype wrapper to implement these?
 pub fn near_zero(v: &Vec3) -> bool {
     let s = 1e-8;
-    v.x < s && v.y < s && v.z < s
+    v.x.abs() < s && v.y.abs() < s && v.z.abs() < s
 }
 
 pub fn reflect(v: &Vec3, normal: &Unit<Vec3>) -> Vec3 {
-    v - 2. * v.dot(&normal) * normal.as_ref()
+    v - 2. * v.dot(normal) * normal.as_ref()
 }
 
 pub fn refract(uv: &Vec3, normal: &Unit<Vec3>, eta_ratio: f64) -> Vec3 {
@@ -55,6 +56,18 @@ pub fn refract(uv: &Vec3, normal: &Unit<Vec3>, eta_ratio: f64) -> Vec3 {
     r_out_perp + r_out_parallel
 }
 
+// a vector given in a basis whose z axis is w, in world coordinates
+pub fn from_onb(w: &Unit<Vec3>, local: &Vec3) -> Vec3 {
+    let a = if w.x.abs() > 0.9 {
+        Vec3::y()
+    } else {
+        Vec3::x()
+    };
+    let v = w.cross(&a).normalize();
+    let u = w.cross(&v);
+    local.x * u + local.y * v + local.z * w.as_ref()
+}
+
 pub fn mul_elemwise(me: &Vec3, other: &Vec3) -> Vec3 {
     Vec3::new(me.x * other.x, me.y * other.y, me.z * other.z)
 }